@@ -0,0 +1,127 @@
+use crate::{interval::Interval, ray::Ray, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        x: Interval::EMPTY,
+        y: Interval::EMPTY,
+        z: Interval::EMPTY,
+    };
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut bbox = Aabb { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    pub fn from_points(a: Vec3, b: Vec3) -> Self {
+        // Treat the two points a and b as extrema for the bounding box, so we don't require a
+        // particular minimum/maximum coordinate order.
+        Aabb::new(
+            Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        )
+    }
+
+    pub fn surrounding(box0: &Aabb, box1: &Aabb) -> Self {
+        Aabb {
+            x: Interval::from_intervals(&box0.x, &box1.x),
+            y: Interval::from_intervals(&box0.y, &box1.y),
+            z: Interval::from_intervals(&box0.z, &box1.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, ray_t: &Interval) -> bool {
+        let ray_orig = r.origin();
+        let ray_dir = r.direction();
+        let mut ray_t = *ray_t;
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / ray_dir[axis];
+
+            let t0 = (ax.min - ray_orig[axis]) * adinv;
+            let t1 = (ax.max - ray_orig[axis]) * adinv;
+
+            ray_t.min = ray_t.min.max(t0.min(t1));
+            ray_t.max = ray_t.max.min(t0.max(t1));
+
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        // Returns the index of the longest axis of the bounding box.
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        Vec3::new(
+            (self.x.min + self.x.max) * 0.5,
+            (self.y.min + self.y.max) * 0.5,
+            (self.z.min + self.z.max) * 0.5,
+        )
+    }
+
+    fn pad_to_minimums(&mut self) {
+        // Adjust the AABB so that no side is narrower than some delta, padding if necessary.
+        let delta = 0.0001;
+        if self.x.size() < delta {
+            self.x = self.x.expand(delta);
+        }
+        if self.y.size() < delta {
+            self.y = self.y.expand(delta);
+        }
+        if self.z.size() < delta {
+            self.z = self.z.expand(delta);
+        }
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::EMPTY
+    }
+}
+
+#[test]
+fn test_hit() {
+    let bbox = Aabb::from_points(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let ray_t = Interval::new(0.001, f32::INFINITY);
+
+    let towards = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    let away = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0));
+    let beside = Ray::new(Vec3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+    assert!(bbox.hit(&towards, &ray_t));
+    assert!(!bbox.hit(&away, &ray_t));
+    assert!(!bbox.hit(&beside, &ray_t));
+}
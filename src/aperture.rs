@@ -0,0 +1,133 @@
+#![allow(clippy::cast_precision_loss)]
+use std::{f32::consts::TAU, io, path::Path, sync::Arc};
+
+use crate::{
+    color::luminance, image::Image, sampling::Distribution2D, util::rand_f32,
+    vec3::rand_in_unit_disk, Vec3,
+};
+
+// Shape of the lens opening, which out-of-focus highlights take on. Points on the aperture are in
+// the square from (-1, -1) to (1, 1), which the camera scales to its defocus disk.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Aperture {
+    #[default]
+    Circular,
+    // A regular polygon inside the unit circle formed by `blades` straight blades, turned
+    // `rotation` degrees anticlockwise. Curvature bows the blades out from straight at 0 to a
+    // full circle at 1.
+    Polygon {
+        blades: u32,
+        rotation: f32,
+        curvature: f32,
+    },
+    // Opening given by the brightness of an image covering the whole square.
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    pub fn polygon(blades: u32, rotation: f32, curvature: f32) -> Self {
+        Aperture::Polygon {
+            blades,
+            rotation,
+            curvature,
+        }
+    }
+
+    pub fn mask(image: Image) -> Self {
+        Aperture::Mask(Arc::new(ApertureMask::new(image)))
+    }
+
+    pub fn load_mask(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::mask(Image::load(path)?))
+    }
+
+    // A point spread evenly over the opening, or by how open it is for a mask.
+    pub fn sample(&self) -> Vec3 {
+        match self {
+            Aperture::Circular => rand_in_unit_disk(),
+            Aperture::Polygon {
+                blades,
+                rotation,
+                curvature,
+            } => {
+                // Blades meet at the corners of a polygon with circumradius 1, and curve out
+                // towards the circle through them in between.
+                let sector = TAU / (*blades).max(3) as f32;
+                let rotation = rotation.to_radians();
+                let curvature = curvature.clamp(0.0, 1.0);
+                loop {
+                    let p = rand_in_unit_disk();
+                    let angle = p.y().atan2(p.x()) - rotation;
+                    let from_middle = angle.rem_euclid(sector) - 0.5 * sector;
+                    let edge = (0.5 * sector).cos() / from_middle.cos();
+                    let radius = edge + curvature * (1.0 - edge);
+                    if p.length_squared() <= radius * radius {
+                        return p;
+                    }
+                }
+            }
+            Aperture::Mask(mask) => mask.sample(),
+        }
+    }
+}
+
+// Aperture image, sampled in proportion to the brightness of its pixels.
+#[derive(Debug, PartialEq)]
+pub struct ApertureMask {
+    distribution: Distribution2D,
+}
+
+impl ApertureMask {
+    pub fn new(image: Image) -> Self {
+        assert!(image.width() > 0 && image.height() > 0);
+        let func: Vec<f32> = (0..image.height())
+            .flat_map(|y| (0..image.width()).map(move |x| (x, y)))
+            .map(|(x, y)| luminance(&image.pixel(x, y)).max(0.0))
+            .collect();
+        ApertureMask {
+            distribution: Distribution2D::new(&func, image.width()),
+        }
+    }
+
+    fn sample(&self) -> Vec3 {
+        let ((u, v), _) = self.distribution.sample((rand_f32(), rand_f32()));
+        Vec3::new(2.0 * u - 1.0, 1.0 - 2.0 * v, 0.0)
+    }
+}
+
+#[test]
+fn test_aperture_sampling() {
+    use std::f32::consts::PI;
+
+    // Straight blades keep samples inside the hexagon, whose nearest edges are at cos 30°.
+    let hexagon = Aperture::polygon(6, 0.0, 0.0);
+    let apothem = (PI / 6.0).cos();
+    let mut widest = 0.0_f32;
+    for _ in 0..2000 {
+        let p = hexagon.sample();
+        for k in 0..6 {
+            let normal_angle = (k as f32 + 0.5) * PI / 3.0;
+            let along = p.x() * normal_angle.cos() + p.y() * normal_angle.sin();
+            assert!(along <= apothem + 1e-5);
+        }
+        widest = widest.max(p.x().abs());
+    }
+    // Corners of the hexagon lie on the x axis.
+    assert!(widest > 0.9);
+
+    // Fully curved blades give back the circle.
+    let round = Aperture::polygon(5, 10.0, 1.0);
+    let outside = (0..2000)
+        .filter(|_| round.sample().length() > apothem)
+        .count();
+    assert!(outside > 200);
+
+    // A mask only opens where it is bright: here the top right quarter.
+    let white = Vec3::new(1.0, 1.0, 1.0);
+    let pixels = vec![Vec3::default(), white, Vec3::default(), Vec3::default()];
+    let mask = Aperture::mask(Image::new(2, 2, pixels));
+    for _ in 0..100 {
+        let p = mask.sample();
+        assert!(p.x() >= 0.0 && p.y() >= 0.0);
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use crate::{environment::EnvironmentMap, ray::Ray, sky::Sky, Vec3};
+
+// Light arriving from rays that leave the scene.
+#[derive(Clone, Default)]
+pub enum Background {
+    // White at the horizon fading to sky blue overhead.
+    #[default]
+    Gradient,
+    Solid(Vec3),
+    Environment(Arc<EnvironmentMap>),
+    Sky(Arc<Sky>),
+}
+
+impl Background {
+    pub fn radiance(&self, r: &Ray) -> Vec3 {
+        let color = match self {
+            Background::Gradient => {
+                let unit_direction = r.direction().normalize();
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                (Vec3::new(1.0, 1.0, 1.0) * (1.0 - a)) + (Vec3::new(0.5, 0.7, 1.0) * a)
+            }
+            Background::Solid(color) => *color,
+            Background::Environment(map) => map.radiance(r.direction()),
+            Background::Sky(sky) => sky.radiance(r.direction()),
+        };
+        r.spectrum(&color)
+    }
+
+    // Samples a direction to light a surface with, and its solid angle density. Only environment
+    // maps and the sun are worth sampling; smooth backgrounds are left to BSDF sampling.
+    pub fn sample(&self) -> Option<(Vec3, f32)> {
+        match self {
+            Background::Environment(map) => Some(map.sample()),
+            Background::Sky(sky) => Some(sky.sample_sun()),
+            _ => None,
+        }
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f32 {
+        match self {
+            Background::Environment(map) => map.pdf(direction),
+            Background::Sky(sky) => sky.sun_pdf(direction),
+            _ => 0.0,
+        }
+    }
+}
+
+impl From<EnvironmentMap> for Background {
+    fn from(map: EnvironmentMap) -> Self {
+        Background::Environment(Arc::new(map))
+    }
+}
+
+impl From<Sky> for Background {
+    fn from(sky: Sky) -> Self {
+        Background::Sky(Arc::new(sky))
+    }
+}
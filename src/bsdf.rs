@@ -0,0 +1,70 @@
+use std::ops::BitOr;
+
+use crate::{hittable::HitRecord, medium::Medium, ray::Ray, Vec3};
+
+// Flags describing which kind of lobe produced a BSDF sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Lobe(u8);
+
+impl Lobe {
+    pub const REFLECTION: Lobe = Lobe(1);
+    pub const TRANSMISSION: Lobe = Lobe(1 << 1);
+    pub const DIFFUSE: Lobe = Lobe(1 << 2);
+    pub const GLOSSY: Lobe = Lobe(1 << 3);
+    pub const SPECULAR: Lobe = Lobe(1 << 4);
+    // The sample depends on the wavelength, so only the hero wavelength of a spectral ray can
+    // follow it.
+    pub const DISPERSIVE: Lobe = Lobe(1 << 5);
+
+    pub fn contains(self, other: Lobe) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_specular(self) -> bool {
+        self.contains(Lobe::SPECULAR)
+    }
+
+    pub fn is_transmission(self) -> bool {
+        self.contains(Lobe::TRANSMISSION)
+    }
+}
+
+impl BitOr for Lobe {
+    type Output = Lobe;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Lobe(self.0 | rhs.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BsdfSample {
+    pub direction: Vec3, // Scattered direction in world space
+    pub weight: Vec3,    // BSDF value times the cosine term, divided by the pdf
+    pub pdf: f32,        // Solid angle density, or the discrete probability for specular lobes
+    pub lobe: Lobe,
+}
+
+/// A shading model. Directions are in world space and `r_in` is the ray that hit the surface, so
+/// the outgoing direction towards the viewer is `-r_in.direction()`.
+///
+/// `eval` returns the BSDF multiplied by the cosine of the scattered direction with the normal.
+/// Specular lobes can't be evaluated, so they only show up through `sample` and have a zero
+/// `eval` and `pdf`.
+pub trait Bsdf: Send + Sync {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample>;
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3;
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32;
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Vec3 {
+        Vec3::default()
+    }
+
+    /// Medium filling the inside of the surface. Rays enter it when a sample transmits through a
+    /// front face and leave it when one transmits back out.
+    fn interior(&self) -> Option<Medium> {
+        None
+    }
+}
@@ -0,0 +1,133 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    hittable_list::{HittableList, HittableObject},
+    interval::Interval,
+    ray::Ray,
+    util::rand_f32,
+    Vec3,
+};
+
+pub struct BvhNode {
+    left: HittableObject,
+    right: HittableObject,
+    bbox: Aabb,
+    left_weight: f32, // Share of the objects below that are on the left, for light sampling
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        let mut objects = list.objects;
+        assert!(!objects.is_empty(), "cannot build a BVH from an empty list");
+        Self::build(&mut objects)
+    }
+
+    fn build(objects: &mut [HittableObject]) -> Self {
+        // Build the bounding box of the span of source objects.
+        let bbox = objects.iter().fold(Aabb::EMPTY, |bbox, object| {
+            Aabb::surrounding(&bbox, &object.bounding_box())
+        });
+
+        let axis = bbox.longest_axis();
+
+        #[allow(clippy::cast_precision_loss)]
+        let left_weight = (objects.len() / 2).max(1) as f32 / objects.len().max(2) as f32;
+        let (left, right) = match objects.len() {
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => (objects[0].clone(), objects[1].clone()),
+            len => {
+                objects.sort_by(|a, b| Self::box_compare(a, b, axis));
+
+                let (first, second) = objects.split_at_mut(len / 2);
+                (
+                    HittableObject::Bvh(Arc::new(Self::build(first))),
+                    HittableObject::Bvh(Arc::new(Self::build(second))),
+                )
+            }
+        };
+
+        BvhNode {
+            left,
+            right,
+            bbox,
+            left_weight,
+        }
+    }
+
+    fn box_compare(a: &HittableObject, b: &HittableObject, axis_index: usize) -> Ordering {
+        let a_axis_interval = *a.bounding_box().axis_interval(axis_index);
+        let b_axis_interval = *b.bounding_box().axis_interval(axis_index);
+        a_axis_interval.min.total_cmp(&b_axis_interval.min)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        if !self.bbox.hit(r, ray_t) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, ray_t, rec);
+        let hit_right = self.right.hit(
+            r,
+            &Interval::new(ray_t.min, if hit_left { rec.t } else { ray_t.max }),
+            rec,
+        );
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Picks each object below with the same probability, as a list of them would.
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        self.left_weight * self.left.pdf_value(origin, direction)
+            + (1.0 - self.left_weight) * self.right.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        if rand_f32() < self.left_weight {
+            self.left.random(origin)
+        } else {
+            self.right.random(origin)
+        }
+    }
+}
+
+#[test]
+fn test_matches_list() {
+    use crate::{material::Material, sphere::Sphere, vec3::random_vec, Vec3};
+
+    let mut list = HittableList::default();
+    for i in 0..50 {
+        let center = Vec3::new((i % 10) as f32, 0.0, (i / 10) as f32);
+        list.add(HittableObject::Sphere(Sphere::new(
+            center,
+            0.3,
+            Material::default(),
+        )));
+    }
+    let bvh = BvhNode::new(list.clone());
+
+    for _ in 0..500 {
+        let r = Ray::new(Vec3::new(4.5, 5.0, 2.0), random_vec());
+        let ray_t = Interval::new(0.001, f32::INFINITY);
+        let (mut list_rec, mut bvh_rec) = (HitRecord::default(), HitRecord::default());
+
+        assert_eq!(
+            list.hit(&r, &ray_t, &mut list_rec),
+            bvh.hit(&r, &ray_t, &mut bvh_rec)
+        );
+        assert_eq!(list_rec.t, bvh_rec.t);
+
+        // Both sample the spheres as lights alike.
+        let origin = Vec3::new(4.5, 5.0, 2.0);
+        let direction = bvh.random(&origin);
+        let pdf = list.pdf_value(&origin, &direction);
+        assert!(pdf > 0.0 && (bvh.pdf_value(&origin, &direction) - pdf).abs() < 1e-3 * pdf);
+    }
+}
@@ -1,30 +1,195 @@
 #![allow(clippy::cast_precision_loss)]
-use std::io::Write;
+use std::{
+    f32::consts::PI,
+    io::{self, ErrorKind, Write},
+    sync::Arc,
+};
 
 use rayon::prelude::*;
 
 use crate::{
+    aperture::Aperture,
+    background::Background,
+    bsdf::{Bsdf, Lobe},
     color::linear_to_gamma,
-    hittable::HitRecord,
-    hittable_list::HittableList,
+    exposure::Exposure,
+    hittable::{HitRecord, Hittable},
     interval::Interval,
+    lens::LensSystem,
+    light::Light,
+    light_sampler::LightSampler,
+    medium::{Medium, MediumSample},
     ray::Ray,
+    sampling::power_heuristic,
+    spectrum::Wavelengths,
     util::rand_f32,
-    vec3::{cross, rand_in_unit_disk},
+    vec3::{cross, dot},
     Vec3,
 };
 
+// Cap on the scattering events of a single random walk through a medium.
+const MAX_WALK_STEPS: u32 = 256;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Projection {
+    // Rays fan out from `look_from` to cover `vfov`.
+    #[default]
+    Perspective,
+    // Parallel rays from a viewport of the given height in world units, centered on `look_from`.
+    Orthographic {
+        height: f32,
+    },
+    // The full sphere of directions in latitude and longitude, for a 2:1 image. The center column
+    // looks at `look_at` and the top row straight along `vup`.
+    Equirectangular,
+    // A circular image of the given field of view in degrees across the image height, up to 360.
+    // Pixels outside the circle stay black.
+    Fisheye {
+        fov: f32,
+        mapping: FisheyeMapping,
+    },
+    // The six 90 degree faces of a cube in a 6:1 strip: right, left, up, down, back and front, or
+    // +x, -x, +y, -y, +z, -z in the camera frame. Each face is what a square pinhole camera would
+    // see looking along its axis, with the up and down faces oriented to join the front face.
+    CubeMap,
+    // Rays traced through the elements of a real lens, focused at `focus_dist` from the film at
+    // `look_from`. The lens and its film set the field of view and depth of field, so `vfov` and
+    // the defocus settings go unused.
+    Lens(Arc<LensSystem>),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FisheyeMapping {
+    // Distance from the image center proportional to the angle off the view direction.
+    #[default]
+    Equidistant,
+    // Area in the image proportional to solid angle.
+    Equisolid,
+}
+
+impl Projection {
+    // Direction in the camera frame, with +x right, +y up and -z forward, through the point (s, t)
+    // of an image with the given aspect ratio. (0, 0) is the upper left corner and (1, 1) the
+    // lower right. None for points outside a fisheye circle, and for the flat projections, which
+    // the camera handles through its viewport.
+    fn panoramic_direction(&self, s: f32, t: f32, aspect_ratio: f32) -> Option<Vec3> {
+        match *self {
+            Projection::Perspective | Projection::Orthographic { .. } | Projection::Lens(_) => None,
+            Projection::Equirectangular => {
+                let phi = (s - 0.5) * 2.0 * PI;
+                let theta = t * PI;
+                Some(Vec3::new(
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    -theta.sin() * phi.cos(),
+                ))
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let x = (2.0 * s - 1.0) * aspect_ratio;
+                let y = 1.0 - 2.0 * t;
+                let radius = (x * x + y * y).sqrt();
+                if radius > 1.0 {
+                    return None;
+                }
+                let half_fov = 0.5 * fov.clamp(0.0, 360.0).to_radians();
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => radius * half_fov,
+                    FisheyeMapping::Equisolid => 2.0 * (radius * (0.5 * half_fov).sin()).asin(),
+                };
+                let phi = y.atan2(x);
+                Some(Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                ))
+            }
+            Projection::CubeMap => {
+                // Forward and up of each face, in strip order.
+                const FACES: [([f32; 3], [f32; 3]); 6] = [
+                    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+                    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+                    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+                    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+                    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+                    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+                ];
+                let strip = (s * 6.0).clamp(0.0, 5.999);
+                #[allow(clippy::cast_possible_truncation)]
+                #[allow(clippy::cast_sign_loss)]
+                let face = strip as usize;
+                let ([fx, fy, fz], [ux, uy, uz]) = FACES[face];
+                let (forward, up) = (Vec3::new(fx, fy, fz), Vec3::new(ux, uy, uz));
+                let right = cross(&forward, &up);
+                let x = 2.0 * (strip - face as f32) - 1.0;
+                let y = 1.0 - 2.0 * t;
+                Some((forward + x * right + y * up).normalize())
+            }
+        }
+    }
+}
+
+// Renders an image for each eye, for viewing in VR. The eyes sit `interocular` apart across the
+// camera, and their views meet at `convergence` along the view, so objects at that distance appear
+// at the depth of the screen. An infinite convergence keeps the eyes parallel.
+//
+// With an equirectangular projection this is omni-directional stereo: the eyes circle the camera
+// center to stay across every direction they look, drawing together towards the poles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    pub interocular: f32,
+    pub convergence: f32,
+    pub layout: StereoLayout,
+}
+
+// Where each eye's image goes in the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StereoLayout {
+    // Left eye on the left, in an image twice as wide.
+    #[default]
+    SideBySide,
+    // Left eye on top, in an image twice as tall.
+    TopBottom,
+}
+
+impl Stereo {
+    pub fn new(interocular: f32, convergence: f32) -> Self {
+        Stereo {
+            interocular,
+            convergence,
+            layout: StereoLayout::default(),
+        }
+    }
+
+    pub fn with_layout(mut self, layout: StereoLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+}
+
 pub struct Camera {
     pub aspect_ratio: f32,      // Ratio of image width over height
     pub image_width: u32,       // Rendered image width in pixel count
     pub samples_per_pixel: u32, // Count of random samples for each pixel
     pub max_depth: u32,         // Maximum number of ray bounces into the scene
     pub vfov: f32,              // Vertical view angle (field of view)
+    pub projection: Projection, // How rays leave the camera
+    pub stereo: Option<Stereo>, // Render a view for each eye
     pub look_from: Vec3,        // Point camera is looking from
     pub look_at: Vec3,          // Point camera is looking at
     pub vup: Vec3,              // Camera-relative "up" direction
     pub defocus_angle: f32,     // Variation angle of rays through each pixel
     pub focus_dist: f32,        // Distance from camera lookfrom point to plane of perfect focus
+    pub shift_x: f32,           // Viewport offset to the right, in viewport widths
+    pub shift_y: f32,           // Viewport offset upwards, in viewport heights
+    pub tilt: f32,              // Degrees the focus plane leans back, top away from the camera
+    pub swing: f32,             // Degrees the focus plane turns, right side away from the camera
+    pub aperture: Aperture,     // Shape of the defocus disk
+    pub cat_eye: f32,           // How far the lens barrel clips the aperture towards the corners
+    pub exposure: Exposure,     // Scale from the light gathered to pixel values
+    pub spectral: bool,         // Trace wavelengths instead of RGB, needed for dispersion
+    pub background: Background, // Light from rays that leave the scene
+    pub lights: Vec<Light>,     // Lights for next event estimation
+    pub light_bvh: bool,        // Pick lights by estimated contribution rather than by power
 
     image_height: u32,          // Rendered image height
     pixel_samples_scale: f32,   // Color scale factor for a sum of pixel samples
@@ -40,38 +205,75 @@ pub struct Camera {
 
     defocus_disk_u: Vec3, // Defocus disk horizontal radius
     defocus_disk_v: Vec3, // Defocus disk vertical radius
+    focus_normal: Vec3,   // Normal of the focus plane, facing the camera
+
+    light_sampler: LightSampler, // Picks one of `lights` at each shading point
+    lens: Option<LensSystem>,    // The lens of a lens projection, focused
 }
 
 impl Camera {
     #[allow(clippy::cast_possible_truncation)]
     #[allow(clippy::cast_sign_loss)]
-    pub fn render(&mut self, world: &HittableList) {
-        self.initialize();
+    pub fn render(&mut self, world: &impl Hittable) -> io::Result<()> {
+        self.initialize()?;
+
+        // Directional lights are weighed by the power they pour onto the whole scene.
+        let bbox = world.bounding_box();
+        let scene_radius = 0.5 * Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size()).length();
+        self.light_sampler = LightSampler::new(self.lights.clone(), self.light_bvh, scene_radius);
+
+        // Stereo renders both eyes' images into one output.
+        let (output_width, output_height) = match self.stereo.map(|stereo| stereo.layout) {
+            None => (self.image_width, self.image_height),
+            Some(StereoLayout::SideBySide) => (2 * self.image_width, self.image_height),
+            Some(StereoLayout::TopBottom) => (self.image_width, 2 * self.image_height),
+        };
 
-        let image: Vec<Vec3> = (0..(self.image_width * self.image_height))
+        let image: Vec<Vec3> = (0..(output_width * output_height))
             .into_par_iter()
             .map(|pixel| {
-                let y = pixel / self.image_width; // Calculate the row (height)
-                let x = pixel % self.image_width; // Calculate the column (width)
+                let y = pixel / output_width; // Calculate the row (height)
+                let x = pixel % output_width; // Calculate the column (width)
+                let (x, y, eye) = match self.stereo.map(|stereo| stereo.layout) {
+                    None => (x, y, 0.0),
+                    Some(StereoLayout::SideBySide) => {
+                        let eye = if x < self.image_width { -1.0 } else { 1.0 };
+                        (x % self.image_width, y, eye)
+                    }
+                    Some(StereoLayout::TopBottom) => {
+                        let eye = if y < self.image_height { -1.0 } else { 1.0 };
+                        (x, y % self.image_height, eye)
+                    }
+                };
                 let mut pixel_color = Vec3::default();
                 for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(x, y);
-                    pixel_color += self.ray_color(&r, self.max_depth, world);
+                    let Some((r, weight)) = self.get_ray(x, y, eye) else {
+                        continue;
+                    };
+                    pixel_color += weight
+                        * if self.spectral {
+                            let wavelengths = Wavelengths::sample(rand_f32());
+                            let r = r.with_wavelengths(Some(wavelengths));
+                            wavelengths.to_rgb(&self.ray_color(&r, world))
+                        } else {
+                            self.ray_color(&r, world)
+                        };
                 }
                 pixel_color * self.pixel_samples_scale
             })
             .collect();
+        let exposure = self
+            .exposure
+            .scale(self.f_number(), &image)
+            .ok_or_else(missing_f_number)?;
 
-        let file = std::fs::File::create("output.ppm").unwrap();
+        let file = std::fs::File::create("output.ppm")?;
         let mut writer = std::io::BufWriter::new(file);
-        write!(
-            writer,
-            "P3\n{} {}\n255\n",
-            self.image_width, self.image_height
-        )
-        .unwrap();
+        write!(writer, "P3\n{output_width} {output_height}\n255\n")?;
 
         for pixel in image {
+            let pixel = pixel * exposure;
+
             // Extract and apply a linear gamma transform for gamma 2
             let (r, g, b) = (
                 linear_to_gamma(pixel.x()),
@@ -84,13 +286,14 @@ impl Camera {
             let gb = (256.0 * g.clamp(0.0, 0.999)) as u32;
             let bb = (256.0 * b.clamp(0.0, 0.999)) as u32;
 
-            writeln!(writer, "{rb} {gb} {bb}").unwrap();
+            writeln!(writer, "{rb} {gb} {bb}")?;
         }
+        Ok(())
     }
 
     #[allow(clippy::cast_possible_truncation)]
     #[allow(clippy::cast_sign_loss)]
-    fn initialize(&mut self) {
+    fn initialize(&mut self) -> io::Result<()> {
         self.image_height = (self.image_width as f32 / self.aspect_ratio) as u32;
         assert!(self.image_height > 1);
 
@@ -98,11 +301,17 @@ impl Camera {
 
         self.center = self.look_from;
 
-        // Determine viewport dimensions.
-        let theta = self.vfov.to_radians();
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h * self.focus_dist;
-        let viewport_width = viewport_height * ((self.image_width / self.image_height) as f32);
+        // Determine viewport dimensions. A perspective viewport sits on the focus plane and an
+        // orthographic one on the camera center. Panoramic projections don't use the viewport.
+        let (viewport_height, viewport_dist) = match self.projection {
+            Projection::Orthographic { height } => (height, 0.0),
+            _ => {
+                let theta = self.vfov.to_radians();
+                let h = (theta / 2.0).tan();
+                (2.0 * h * self.focus_dist, self.focus_dist)
+            }
+        };
+        let viewport_width = viewport_height * (self.image_width as f32 / self.image_height as f32);
 
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
         self.w = (self.look_from - self.look_at).normalize();
@@ -117,9 +326,11 @@ impl Camera {
         self.pixel_delta_u = viewport_u / self.image_width;
         self.pixel_delta_v = viewport_v / self.image_height;
 
-        // Calculate the location of the upper left pixel.
+        // Calculate the location of the upper left pixel. Shifting the viewport aims the view
+        // elsewhere without turning the camera, so lines parallel to the viewport stay parallel.
+        let shift = self.shift_x * viewport_u - self.shift_y * viewport_v;
         let viewport_upper_left =
-            self.center - (self.focus_dist * self.w) - viewport_u / 2.0 - viewport_v / 2.0;
+            self.center - (viewport_dist * self.w) - viewport_u / 2.0 - viewport_v / 2.0 + shift;
 
         self.upper_left_pixel_loc =
             viewport_upper_left + ((self.pixel_delta_u + self.pixel_delta_v) * 0.5);
@@ -128,22 +339,137 @@ impl Camera {
         let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
         self.defocus_disk_u = self.u * defocus_radius;
         self.defocus_disk_v = self.v * defocus_radius;
+
+        // Tilting and swinging the lens turns the focus plane about the point in focus straight
+        // ahead.
+        let (tilt, swing) = (self.tilt.to_radians(), self.swing.to_radians());
+        let focus_up = tilt.cos() * self.v - tilt.sin() * self.w;
+        let focus_right = swing.cos() * self.u - swing.sin() * self.w;
+        self.focus_normal = cross(&focus_right, &focus_up).normalize();
+
+        self.lens = match &self.projection {
+            Projection::Lens(lens) => Some(lens.focused(self.focus_dist / lens.scale())),
+            _ => None,
+        };
+
+        // Catch a physical exposure with no f-number to go on before rendering rather than after.
+        if self.exposure.scale(self.f_number(), &[]).is_none() {
+            return Err(missing_f_number());
+        }
+        Ok(())
     }
 
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
+    // f-number of the lens of a lens projection. Otherwise that of a full frame camera, with a
+    // 24 mm tall sensor, seeing the same view through the same defocus disk, taking world units as
+    // metres. None for a pinhole camera.
+    fn f_number(&self) -> Option<f32> {
+        if let Some(lens) = &self.lens {
+            return lens.f_number();
+        }
+        let focal_length = 12.0 / (0.5 * self.vfov.to_radians()).tan();
+        let aperture = 2000.0 * self.focus_dist * (0.5 * self.defocus_angle.to_radians()).tan();
+        (aperture > 0.0).then(|| focal_length / aperture)
+    }
+
+    // Returns the ray with the weight of the light it brings back, which only a lens projection
+    // lowers. `eye` is -1 for the left eye, 1 for the right and 0 without stereo. None where the
+    // projection covers no directions, outside a fisheye circle, or the lens barrel blocks the
+    // aperture.
+    fn get_ray(&self, i: u32, j: u32, eye: f32) -> Option<(Ray, f32)> {
         // Construct a camera ray originating from the defocus disk directed at randomly sampled
         // point around the pixel location (i, j).
         let offset = Self::sample_square();
+
+        // How far the eye sits to the right of the camera center, and where its view meets the
+        // other eye's.
+        let (eye_offset, convergence) = self.stereo.map_or((0.0, f32::INFINITY), |stereo| {
+            (0.5 * eye * stereo.interocular, stereo.convergence)
+        });
+
+        if let Projection::Lens(_) = self.projection {
+            return self.lens_ray(i, j, &offset, eye_offset);
+        }
+        if !matches!(
+            self.projection,
+            Projection::Perspective | Projection::Orthographic { .. }
+        ) {
+            // Panoramic projections map the pixel straight to a direction from the camera center.
+            let s = (i as f32 + 0.5 + offset.x()) / self.image_width as f32;
+            let t = (j as f32 + 0.5 + offset.y()) / self.image_height as f32;
+            let aspect_ratio = self.image_width as f32 / self.image_height as f32;
+            let d = self.projection.panoramic_direction(s, t, aspect_ratio)?;
+
+            // Omni-directional stereo keeps the eyes across the horizontal part of each direction,
+            // which shrinks to nothing at the poles.
+            let eye = if self.projection == Projection::Equirectangular {
+                eye_offset * Vec3::new(-d.z(), 0.0, d.x())
+            } else {
+                Vec3::new(eye_offset, 0.0, 0.0)
+            };
+            let d = d - eye / convergence;
+
+            let origin = self.center + eye.x() * self.u + eye.z() * self.w;
+            let direction = d.x() * self.u + d.y() * self.v + d.z() * self.w;
+            return Some((Ray::new(origin, direction), 1.0));
+        }
+
         let pixel_sample = self.upper_left_pixel_loc
             + ((i as f32 + offset.x()) * self.pixel_delta_u)
             + ((j as f32 + offset.y()) * self.pixel_delta_v);
+        let eye = eye_offset * self.u;
+        if let Projection::Orthographic { .. } = self.projection {
+            return Some((Ray::new(pixel_sample + eye, -self.w), 1.0));
+        }
+
+        // The point in focus is where the view through the pixel sample meets the focus plane,
+        // which is on the viewport unless the plane is tilted. Shift the eye's point in focus so
+        // its view still crosses the center's at the convergence distance.
+        let view = pixel_sample - self.center;
+        let t =
+            -self.focus_dist * dot(&self.w, &self.focus_normal) / dot(&view, &self.focus_normal);
+        // Views running alongside or away from the plane are focused far off.
+        let t = if t > 0.0 { t.min(1e6) } else { 1e6 };
+        let focus = self.center + t * view + eye * (1.0 - t * self.focus_dist / convergence);
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            self.defocus_disk_sample()
-        };
-        let ray_direction = pixel_sample - ray_origin;
-        Ray::new(ray_origin, ray_direction)
+            let p = self.aperture.sample();
+            // Off axis, the lens barrel cuts into the aperture from the side, more so further
+            // out, leaving cat's eye shaped highlights and darker corners. `to_corner` runs from
+            // the image center to 1 at the corners.
+            if self.cat_eye > 0.0 {
+                let aspect_ratio = self.image_width as f32 / self.image_height as f32;
+                let x = (2.0 * (i as f32 + 0.5) / self.image_width as f32 - 1.0) * aspect_ratio;
+                let y = 1.0 - 2.0 * (j as f32 + 0.5) / self.image_height as f32;
+                let to_corner = Vec3::new(x, y, 0.0) / (aspect_ratio * aspect_ratio + 1.0).sqrt();
+                if (p - self.cat_eye * to_corner).length_squared() > 1.0 {
+                    return None;
+                }
+            }
+            self.defocus_disk_sample(&p)
+        } + eye;
+        let ray_direction = focus - ray_origin;
+        Some((Ray::new(ray_origin, ray_direction), 1.0))
+    }
+
+    // Traces a ray from the point on the film for the pixel sample out through the lens.
+    fn lens_ray(&self, i: u32, j: u32, offset: &Vec3, eye_offset: f32) -> Option<(Ray, f32)> {
+        let lens = self.lens.as_ref()?;
+        let aspect_ratio = self.image_width as f32 / self.image_height as f32;
+        let film_width =
+            lens.film_diagonal() * aspect_ratio / (aspect_ratio * aspect_ratio + 1.0).sqrt();
+        let film_height = film_width / aspect_ratio;
+
+        // The lens turns the image over, so the top left of the image is the bottom right of the
+        // film.
+        let s = (i as f32 + 0.5 + offset.x()) / self.image_width as f32;
+        let t = (j as f32 + 0.5 + offset.y()) / self.image_height as f32;
+        let film = Vec3::new((0.5 - s) * film_width, (t - 0.5) * film_height, 0.0);
+        let (origin, direction, weight) = lens.sample_ray(film)?;
+
+        let to_world = |v: Vec3| v.x() * self.u + v.y() * self.v + v.z() * self.w;
+        let origin = self.center + eye_offset * self.u + lens.scale() * to_world(origin);
+        Some((Ray::new(origin, to_world(direction)), weight))
     }
 
     fn sample_square() -> Vec3 {
@@ -151,36 +477,154 @@ impl Camera {
         Vec3::new(rand_f32() - 0.5, rand_f32() - 0.5, 0.0)
     }
 
-    fn defocus_disk_sample(&self) -> Vec3 {
-        // Returns a random point in the camera defocus disk.
-        let p = rand_in_unit_disk();
+    fn defocus_disk_sample(&self, p: &Vec3) -> Vec3 {
+        // Returns the point of the camera defocus disk at aperture point `p`.
         self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
     }
 
-    #[allow(clippy::only_used_in_recursion)]
-    fn ray_color(&self, r: &Ray, depth: u32, world: &HittableList) -> Vec3 {
-        // If we've exceeded the ray bounce limit, no more light is gathered.
-        if depth == 0 {
+    fn ray_color(&self, r: &Ray, world: &impl Hittable) -> Vec3 {
+        let mut r = *r;
+        let mut rec = HitRecord::default();
+        let mut color = Vec3::default();
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        let mut medium: Option<Medium> = None;
+        let mut depth = 0;
+        let mut walk_steps = 0;
+        // Density of the BSDF sample that produced `r`, for weighting emitters and the background
+        // against light sampling. None after specular bounces and medium scattering, which light
+        // sampling misses.
+        let mut bsdf_pdf: Option<f32> = None;
+
+        loop {
+            if !world.hit(&r, &Interval::new(0.001, f32::INFINITY), &mut rec) {
+                let weight = bsdf_pdf.map_or(1.0, |pdf| {
+                    power_heuristic(pdf, self.background.pdf(r.direction()))
+                });
+                return color + throughput * weight * self.background.radiance(&r);
+            }
+
+            // Inside a medium, random walk through it until the ray reaches the boundary.
+            if let Some(medium) = medium {
+                match medium.sample(&r, rec.t, &throughput) {
+                    MediumSample::Scatter { t, weight } => {
+                        walk_steps += 1;
+                        if walk_steps > MAX_WALK_STEPS {
+                            return color;
+                        }
+                        throughput *= weight;
+                        r = Ray::new(r.at(t), medium.sample_phase(r.direction()))
+                            .with_wavelengths(r.wavelengths());
+                        bsdf_pdf = None;
+                        continue;
+                    }
+                    MediumSample::Pass { weight } => throughput *= weight,
+                }
+            }
+
+            let emitted = rec.mat.emitted(&r, &rec);
+            if emitted != Vec3::default() {
+                let weight = bsdf_pdf.map_or(1.0, |pdf| {
+                    power_heuristic(pdf, self.light_sampler.pdf(&r, &rec))
+                });
+                color += throughput * weight * emitted;
+            }
+
+            // If we've exceeded the ray bounce limit, no more light is gathered.
+            depth += 1;
+            if depth >= self.max_depth {
+                return color;
+            }
+
+            let Some(srec) = rec.mat.sample(&r, &rec) else {
+                return color;
+            };
+
+            let mut wavelengths = r.wavelengths();
+            if let Some(wavelengths) = wavelengths.as_mut() {
+                if srec.lobe.contains(Lobe::DISPERSIVE) {
+                    throughput *= wavelengths.terminate_secondary();
+                    r = r.with_wavelengths(Some(*wavelengths));
+                }
+            }
+
+            color += throughput * self.sample_background(&r, &rec, world);
+            color += throughput * self.sample_lights(&r, &rec, world);
+
+            // Transmission through a surface moves the ray into or out of the medium it encloses.
+            medium = match (srec.lobe.is_transmission(), rec.front_face) {
+                (true, true) => rec.mat.interior(),
+                (true, false) => None,
+                (false, _) => medium,
+            };
+
+            bsdf_pdf = if srec.lobe.is_specular() {
+                None
+            } else {
+                Some(rec.mat.pdf(&r, &rec, &srec.direction))
+            };
+            throughput *= srec.weight;
+            r = Ray::new(rec.p, srec.direction).with_wavelengths(wavelengths);
+        }
+    }
+
+    // Next event estimation: light from a direction sampled on the background, weighted against the
+    // chance of BSDF sampling finding it.
+    fn sample_background(&self, r: &Ray, rec: &HitRecord, world: &impl Hittable) -> Vec3 {
+        let Some((direction, pdf)) = self.background.sample() else {
+            return Vec3::default();
+        };
+        if pdf <= 0.0 {
             return Vec3::default();
         }
 
-        let mut rec = HitRecord::default();
+        let f = rec.mat.eval(r, rec, &direction);
+        if f == Vec3::default() {
+            return Vec3::default();
+        }
 
-        if world.hit(r, &Interval::new(0.001, f32::INFINITY), &mut rec) {
-            let mut scattered = Ray::default();
-            let mut attenuation = Vec3::default();
+        let shadow_ray = Ray::new(rec.p, direction).with_wavelengths(r.wavelengths());
+        if world.hit(
+            &shadow_ray,
+            &Interval::new(0.001, f32::INFINITY),
+            &mut HitRecord::default(),
+        ) {
+            return Vec3::default();
+        }
 
-            if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-                return attenuation * self.ray_color(&scattered, depth - 1, world);
-            }
+        let weight = power_heuristic(pdf, rec.mat.pdf(r, rec, &direction));
+        f * self.background.radiance(&shadow_ray) * weight / pdf
+    }
+
+    // Next event estimation: light from one of the lights, picked by the light sampler. Area lights
+    // are weighted against the chance of BSDF sampling hitting them.
+    fn sample_lights(&self, r: &Ray, rec: &HitRecord, world: &impl Hittable) -> Vec3 {
+        let Some((light, pmf)) = self.light_sampler.sample(&rec.p) else {
+            return Vec3::default();
+        };
+        let Some(sample) = light.sample(&rec.p) else {
+            return Vec3::default();
+        };
 
+        let f = rec.mat.eval(r, rec, &sample.direction);
+        if f == Vec3::default() {
             return Vec3::default();
         }
 
-        let unit_direction = r.direction().normalize();
-        let a = 0.5 * (unit_direction.y() + 1.0);
+        let shadow_ray = Ray::new(rec.p, sample.direction);
+        if world.hit(
+            &shadow_ray,
+            &Interval::new(0.001, sample.distance - 0.001),
+            &mut HitRecord::default(),
+        ) {
+            return Vec3::default();
+        }
 
-        (Vec3::new(1.0, 1.0, 1.0) * (1.0 - a)) + (Vec3::new(0.5, 0.7, 1.0) * a)
+        let radiance = f * r.spectrum(&sample.radiance);
+        if light.is_delta() {
+            return radiance / pmf;
+        }
+        let pdf = pmf * sample.pdf;
+        radiance * power_heuristic(pdf, rec.mat.pdf(r, rec, &sample.direction)) / pdf
     }
 }
 
@@ -192,6 +636,8 @@ impl Default for Camera {
             samples_per_pixel: 10,
             max_depth: 10,
             vfov: f32::default(),
+            projection: Projection::default(),
+            stereo: None,
             vup: Vec3::default(),
             look_at: Vec3::default(),
             look_from: Vec3::default(),
@@ -206,8 +652,220 @@ impl Default for Camera {
             pixel_delta_v: Vec3::default(),
             defocus_angle: f32::default(),
             focus_dist: f32::default(),
+            shift_x: 0.0,
+            shift_y: 0.0,
+            tilt: 0.0,
+            swing: 0.0,
+            aperture: Aperture::default(),
+            cat_eye: 0.0,
+            exposure: Exposure::default(),
+            spectral: false,
+            background: Background::default(),
+            lights: Vec::new(),
+            light_bvh: false,
             defocus_disk_u: Vec3::default(),
             defocus_disk_v: Vec3::default(),
+            focus_normal: Vec3::default(),
+            light_sampler: LightSampler::default(),
+            lens: None,
         }
     }
 }
+
+fn missing_f_number() -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        "physical exposure needs an f-number, a defocus disk or a lens with a stop",
+    )
+}
+
+#[test]
+fn test_orthographic_rays() {
+    let mut cam = Camera {
+        image_width: 200,
+        aspect_ratio: 2.0,
+        projection: Projection::Orthographic { height: 4.0 },
+        look_from: Vec3::new(0.0, 0.0, 5.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        ..Default::default()
+    };
+    cam.initialize().unwrap();
+
+    // Corner pixels of an 8 by 4 viewport are 0.04 across, so their rays start within half a pixel
+    // of the pixel centres, and all look down -z.
+    let top_left = cam.get_ray(0, 0, 0.0).unwrap().0;
+    let bottom_right = cam.get_ray(199, 99, 0.0).unwrap().0;
+    let within_half_pixel = |p: &Vec3, center: Vec3| {
+        (p.x() - center.x()).abs() <= 0.0201 && (p.y() - center.y()).abs() <= 0.0201
+    };
+    assert!(within_half_pixel(
+        top_left.origin(),
+        Vec3::new(-3.98, 1.98, 5.0)
+    ));
+    assert!(within_half_pixel(
+        bottom_right.origin(),
+        Vec3::new(3.98, -1.98, 5.0)
+    ));
+    for r in [top_left, bottom_right] {
+        assert!((r.direction().normalize() - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-6);
+    }
+}
+
+#[test]
+fn test_panoramic_rays() {
+    let forward = Vec3::new(0.0, 0.0, -1.0);
+    let close = |a: Option<Vec3>, b: Vec3| (a.unwrap() - b).length() < 1e-5;
+
+    let equirectangular = Projection::Equirectangular;
+    assert!(close(
+        equirectangular.panoramic_direction(0.5, 0.5, 2.0),
+        forward
+    ));
+    assert!(close(
+        equirectangular.panoramic_direction(0.75, 0.5, 2.0),
+        Vec3::new(1.0, 0.0, 0.0)
+    ));
+    assert!(close(
+        equirectangular.panoramic_direction(0.3, 0.0, 2.0),
+        Vec3::new(0.0, 1.0, 0.0)
+    ));
+
+    // The edge of a 180 degree fisheye circle looks sideways, whatever the mapping.
+    for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+        let fisheye = Projection::Fisheye {
+            fov: 180.0,
+            mapping,
+        };
+        assert!(close(fisheye.panoramic_direction(0.5, 0.5, 1.0), forward));
+        assert!(close(
+            fisheye.panoramic_direction(0.5, 0.0, 1.0),
+            Vec3::new(0.0, 1.0, 0.0)
+        ));
+        assert!(fisheye.panoramic_direction(0.0, 0.0, 1.0).is_none());
+    }
+
+    // Face centers look along the axes, and the up face's bottom edge meets the front face's top.
+    let cube = Projection::CubeMap;
+    assert!(close(
+        cube.panoramic_direction(1.0 / 12.0, 0.5, 6.0),
+        Vec3::new(1.0, 0.0, 0.0)
+    ));
+    assert!(close(
+        cube.panoramic_direction(11.0 / 12.0, 0.5, 6.0),
+        forward
+    ));
+    let up_bottom = cube.panoramic_direction(5.0 / 12.0, 1.0, 6.0).unwrap();
+    let front_top = cube.panoramic_direction(11.0 / 12.0, 0.0, 6.0).unwrap();
+    assert!((up_bottom - front_top).length() < 1e-5);
+}
+
+#[test]
+fn test_stereo_rays() {
+    let mut cam = Camera {
+        image_width: 101,
+        aspect_ratio: 1.0,
+        vfov: 90.0,
+        focus_dist: 2.0,
+        stereo: Some(Stereo::new(1.0, 5.0)),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        look_at: Vec3::new(0.0, 0.0, -1.0),
+        ..Default::default()
+    };
+    let meet = Vec3::new(0.0, 0.0, -5.0);
+
+    // Through the middle pixel, each eye looks from its side at the convergence point.
+    cam.initialize().unwrap();
+    for eye in [-1.0, 1.0] {
+        let (r, _) = cam.get_ray(50, 50, eye).unwrap();
+        assert!((*r.origin() - Vec3::new(0.5 * eye, 0.0, 0.0)).length() < 1e-6);
+        let at_meet = r.at(5.0 / -r.direction().z());
+        assert!((at_meet - meet).length() < 0.1);
+    }
+
+    // Omni-directional eyes sit across the view sideways, and together straight up.
+    cam.projection = Projection::Equirectangular;
+    cam.aspect_ratio = 2.0;
+    cam.image_width = 202;
+    cam.initialize().unwrap();
+    let (right, _) = cam.get_ray(151, 50, 1.0).unwrap();
+    assert!((*right.origin() - Vec3::new(0.0, 0.0, 0.5)).length() < 0.02);
+    let (up, _) = cam.get_ray(0, 0, 1.0).unwrap();
+    assert!(up.origin().length() < 0.02);
+}
+
+#[test]
+fn test_lens_shift_and_tilt() {
+    let mut cam = Camera {
+        image_width: 1001,
+        aspect_ratio: 1.0,
+        vfov: 90.0,
+        focus_dist: 1.0,
+        shift_y: 0.25,
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        look_at: Vec3::new(0.0, 0.0, -1.0),
+        ..Default::default()
+    };
+
+    // Shifting up a quarter of the 2 unit high viewport looks up without tilting the camera.
+    cam.initialize().unwrap();
+    let (r, _) = cam.get_ray(500, 500, 0.0).unwrap();
+    let d = r.direction();
+    assert!((d.y() / -d.z() - 0.5).abs() < 0.01);
+
+    // With the focus plane tilted back 30 degrees, rays from all over a wide aperture through
+    // the top middle pixel meet where its view reaches the plane, further than the focus
+    // distance.
+    cam.shift_y = 0.0;
+    cam.tilt = 30.0;
+    cam.defocus_angle = 30.0;
+    cam.initialize().unwrap();
+    let (sin, cos) = 30.0_f32.to_radians().sin_cos();
+    let t = cos / (cos - sin);
+    let focus = Vec3::new(0.0, t, -t);
+    for _ in 0..20 {
+        let (r, _) = cam.get_ray(500, 0, 0.0).unwrap();
+        let to_focus = focus - *r.origin();
+        let along = dot(&to_focus, &r.direction().normalize());
+        assert!((to_focus - along * r.direction().normalize()).length() < 0.02);
+    }
+}
+
+#[test]
+fn test_f_number() {
+    // A 50 mm lens on full frame sees 2 atan(12 / 50) vertically, and a 12.5 mm aperture at
+    // 10 m spans 2 atan(6.25 mm / 10 m): f/4.
+    let mut cam = Camera {
+        image_width: 100,
+        aspect_ratio: 1.0,
+        vfov: 2.0 * (12.0_f32 / 50.0).atan().to_degrees(),
+        focus_dist: 10.0,
+        ..Default::default()
+    };
+    cam.initialize().unwrap();
+    assert_eq!(cam.f_number(), None);
+    cam.defocus_angle = 2.0 * (0.006_25_f32 / 10.0).atan().to_degrees();
+    cam.initialize().unwrap();
+    assert!((cam.f_number().unwrap() - 4.0).abs() < 0.01);
+
+    // A lens projection goes by the lens instead, here a 100 mm biconvex lens stopped at 10 mm.
+    let lens = LensSystem::parse("100 5 1.5 20\n-100 2 1 20\n0 95 0 10\n").unwrap();
+    cam.projection = Projection::Lens(Arc::new(lens));
+    cam.initialize().unwrap();
+    assert!((cam.f_number().unwrap() - 10.0).abs() < 0.3);
+
+    // Physical exposure without an f-number is an error for a pinhole camera or a lens with no
+    // stop, before anything renders.
+    cam.exposure = Exposure::Physical {
+        iso: 100.0,
+        shutter: 0.01,
+        f_number: None,
+    };
+    cam.initialize().unwrap();
+    let no_stop = LensSystem::parse("100 5 1.5 20\n-100 95 1 20\n").unwrap();
+    cam.projection = Projection::Lens(Arc::new(no_stop));
+    let error = cam.initialize().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    cam.projection = Projection::Perspective;
+    cam.defocus_angle = 0.0;
+    assert!(cam.initialize().is_err());
+}
@@ -1,3 +1,5 @@
+use crate::Vec3;
+
 pub fn linear_to_gamma(linear_component: f32) -> f32 {
     // match linear_component > 0.0 {
     //     true => linear_component.sqrt(),
@@ -10,3 +12,12 @@ pub fn linear_to_gamma(linear_component: f32) -> f32 {
         0.0
     }
 }
+
+pub fn luminance(color: &Vec3) -> f32 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+// Inverse of `linear_to_gamma`, for colours read from 8-bit images.
+pub fn gamma_to_linear(gamma_component: f32) -> f32 {
+    gamma_component * gamma_component
+}
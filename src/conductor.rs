@@ -0,0 +1,177 @@
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Lobe},
+    fresnel::{fr_complex_rgb, Complex, ThinFilm},
+    hittable::HitRecord,
+    microfacet::{cos_theta, reflect_local, TrowbridgeReitz},
+    ray::Ray,
+    util::rand_f32,
+    vec3::{dot, Vec3},
+};
+
+// Metals with measured complex indices of refraction, sampled at roughly 650, 550 and 450nm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasuredMetal {
+    Aluminium,
+    Chromium,
+    Copper,
+    Gold,
+    Iron,
+    Nickel,
+    Platinum,
+    Silver,
+    Titanium,
+}
+
+impl MeasuredMetal {
+    // Returns the (eta, k) pair of the metal.
+    pub fn eta_k(&self) -> (Vec3, Vec3) {
+        let (eta, k) = match self {
+            MeasuredMetal::Aluminium => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            MeasuredMetal::Chromium => ([3.105, 3.180, 2.210], [3.320, 3.330, 3.230]),
+            MeasuredMetal::Copper => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+            MeasuredMetal::Gold => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+            MeasuredMetal::Iron => ([2.912, 2.950, 2.585], [3.089, 2.934, 2.767]),
+            MeasuredMetal::Nickel => ([1.990, 1.810, 1.670], [3.740, 3.290, 2.840]),
+            MeasuredMetal::Platinum => ([2.380, 2.080, 1.850], [4.260, 3.720, 3.135]),
+            MeasuredMetal::Silver => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+            MeasuredMetal::Titanium => ([2.741, 2.541, 2.267], [3.814, 3.435, 3.039]),
+        };
+        (
+            Vec3::new(eta[0], eta[1], eta[2]),
+            Vec3::new(k[0], k[1], k[2]),
+        )
+    }
+}
+
+// Rough conductor with a GGX microfacet distribution and complex Fresnel reflectance.
+// Anisotropic roughness is aligned with the tangent of the shading frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Conductor {
+    pub eta: Vec3,
+    pub k: Vec3,
+    pub distrib: TrowbridgeReitz,
+    pub thin_film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3, k: Vec3, roughness: f32, anisotropy: f32) -> Self {
+        Conductor {
+            eta,
+            k,
+            distrib: TrowbridgeReitz::from_roughness(roughness, anisotropy),
+            thin_film: None,
+        }
+    }
+
+    pub fn measured(metal: MeasuredMetal, roughness: f32, anisotropy: f32) -> Self {
+        let (eta, k) = metal.eta_k();
+        Self::new(eta, k, roughness, anisotropy)
+    }
+
+    // Coat the metal with a thin film, like an oxide layer or heat tint.
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    // Fresnel reflectance, with the index of refraction at the ray's wavelengths in spectral mode.
+    fn fresnel(&self, r_in: &Ray, cos_theta: f32) -> Vec3 {
+        let eta = r_in.interpolate(&self.eta);
+        let k = r_in.interpolate(&self.k);
+        let Some(film) = &self.thin_film else {
+            return fr_complex_rgb(cos_theta.abs(), &eta, &k);
+        };
+
+        let lambda = r_in.channel_wavelengths();
+        let r = |i: usize| {
+            film.reflectance(cos_theta.abs(), 1.0, Complex::new(eta[i], k[i]), lambda[i])
+        };
+        Vec3::new(r(0), r(1), r(2))
+    }
+
+    // BSDF value for a pair of local directions on the same side as the normal.
+    fn f(&self, r_in: &Ray, wo: &Vec3, wi: &Vec3) -> Vec3 {
+        let cos_theta_o = cos_theta(wo).abs();
+        let cos_theta_i = cos_theta(wi).abs();
+        if cos_theta_i == 0.0 || cos_theta_o == 0.0 {
+            return Vec3::default();
+        }
+        let wm = *wi + *wo;
+        if wm.length_squared() == 0.0 {
+            return Vec3::default();
+        }
+        let wm = wm.normalize();
+
+        let f = self.fresnel(r_in, dot(wo, &wm));
+        self.distrib.d(&wm) * f * self.distrib.g(wo, wi) / (4.0 * cos_theta_i * cos_theta_o)
+    }
+}
+
+impl Bsdf for Conductor {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        if cos_theta(&wo) <= 0.0 {
+            return None;
+        }
+
+        if self.distrib.effectively_smooth() {
+            // Sample perfect specular reflection.
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            return Some(BsdfSample {
+                direction: uvw.transform(&wi),
+                weight: self.fresnel(r_in, cos_theta(&wi)),
+                pdf: 1.0,
+                lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+            });
+        }
+
+        // Sample a visible microfacet normal and reflect about it.
+        let wm = self.distrib.sample_wm(&wo, (rand_f32(), rand_f32()));
+        let wi = reflect_local(&wo, &wm);
+        if cos_theta(&wi) <= 0.0 {
+            return None;
+        }
+
+        let pdf = self.distrib.pdf(&wo, &wm) / (4.0 * dot(&wo, &wm).abs());
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            direction: uvw.transform(&wi),
+            weight: self.f(r_in, &wo, &wi) * (cos_theta(&wi) / pdf),
+            pdf,
+            lobe: Lobe::GLOSSY | Lobe::REFLECTION,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        if self.distrib.effectively_smooth() {
+            return Vec3::default();
+        }
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        if cos_theta(&wo) <= 0.0 || cos_theta(&wi) <= 0.0 {
+            return Vec3::default();
+        }
+
+        self.f(r_in, &wo, &wi) * cos_theta(&wi)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        if self.distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        if cos_theta(&wo) <= 0.0 || cos_theta(&wi) <= 0.0 {
+            return 0.0;
+        }
+
+        let wm = (wo + wi).normalize();
+        self.distrib.pdf(&wo, &wm) / (4.0 * dot(&wo, &wm).abs())
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Lobe},
+    fresnel::{fr_dielectric, ThinFilm},
+    hittable::HitRecord,
+    microfacet::{cos_theta, reflect_local, refract_local, TrowbridgeReitz},
+    ray::Ray,
+    util::rand_f32,
+    vec3::{dot, reflect, Vec3},
+};
+
+// Index of refraction, optionally varying with wavelength for dispersion. Only spectral renders
+// show dispersion; RGB renders use the index at the sodium d-line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ior {
+    Constant(f32),
+    // Cauchy's equation n = a + b / λ², with λ in micrometers.
+    Cauchy { a: f32, b: f32 },
+    // Sellmeier's equation n² = 1 + Σ b λ² / (λ² - c), with λ in micrometers.
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Ior {
+    // Schott N-BK7 crown glass.
+    pub const CROWN_GLASS: Ior = Ior::Sellmeier {
+        b: [1.039_612, 0.231_792_34, 1.010_469_5],
+        c: [0.006_000_699, 0.020_017_914, 103.560_65],
+    };
+    pub const DIAMOND: Ior = Ior::Sellmeier {
+        b: [0.3306, 4.3356, 0.0],
+        c: [0.030_625, 0.011_236, 0.0],
+    };
+
+    // Wavelength of the sodium d-line in nanometers, the usual reference for a single index.
+    const D_LINE: f32 = 587.6;
+
+    // Index of refraction at a wavelength in nanometers.
+    pub fn at(&self, lambda: f32) -> f32 {
+        let um = lambda / 1000.0;
+        let um2 = um * um;
+        match self {
+            Ior::Constant(n) => *n,
+            Ior::Cauchy { a, b } => a + b / um2,
+            Ior::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * um2 / (um2 - c[i])).sum::<f32>();
+                n2.sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+
+    // Index for the ray's hero wavelength, or at the d-line for RGB rays.
+    fn for_ray(&self, r: &Ray) -> f32 {
+        match r.wavelengths() {
+            Some(wavelengths) => self.at(wavelengths.hero()),
+            None => self.at(Self::D_LINE),
+        }
+    }
+}
+
+impl From<f32> for Ior {
+    fn from(value: f32) -> Self {
+        Ior::Constant(value)
+    }
+}
+
+// Glass-like material with GGX roughness, exact Fresnel and Beer-Lambert absorption for the light
+// travelling through its interior. A roughness of zero gives perfectly smooth glass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoughDielectric {
+    pub refraction_index: Ior,
+    pub distrib: TrowbridgeReitz,
+    pub absorption: Vec3, // Absorption coefficient per unit distance inside the medium
+    pub thin_film: Option<ThinFilm>,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: impl Into<Ior>, roughness: f32) -> Self {
+        RoughDielectric {
+            refraction_index: refraction_index.into(),
+            distrib: TrowbridgeReitz::from_roughness(roughness, 0.0),
+            absorption: Vec3::default(),
+            thin_film: None,
+        }
+    }
+
+    // Coat the surface with a thin film for iridescence.
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    // Tint the glass so that light keeps `color` of its energy after travelling `distance`.
+    pub fn with_color(mut self, color: Vec3, distance: f32) -> Self {
+        let absorb = |c: f32| -c.clamp(1e-4, 1.0).ln() / distance;
+        self.absorption = Vec3::new(absorb(color.x()), absorb(color.y()), absorb(color.z()));
+        self
+    }
+
+    // Optical interface the ray sees, oriented for the side it arrived from.
+    fn interface(&self, r_in: &Ray, rec: &HitRecord) -> Interface {
+        let n = self.refraction_index.for_ray(r_in);
+        let (n_i, n_t) = if rec.front_face { (1.0, n) } else { (n, 1.0) };
+        Interface {
+            eta: n_t / n_i,
+            n_i,
+            n_t,
+            lambda: r_in.channel_wavelengths(),
+            thin_film: self.thin_film,
+        }
+    }
+
+    // Beer-Lambert attenuation for rays that travelled through the interior to reach this hit.
+    fn transmittance(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        if rec.front_face {
+            return Vec3::new(1.0, 1.0, 1.0);
+        }
+        let distance = rec.t * r_in.direction().length();
+        let transmit = |sigma_a: f32| f32::exp(-sigma_a * distance);
+        r_in.spectrum(&Vec3::new(
+            transmit(self.absorption.x()),
+            transmit(self.absorption.y()),
+            transmit(self.absorption.z()),
+        ))
+    }
+
+    // Generalized half vector for a pair of local directions, or None for degenerate
+    // configurations and back-facing microfacets.
+    fn half_vector(wo: &Vec3, wi: &Vec3, eta: f32) -> Option<Vec3> {
+        let reflect = cos_theta(wi) > 0.0;
+        let etap = if reflect { 1.0 } else { eta };
+        let wm = *wi * etap + *wo;
+        if cos_theta(wi) == 0.0 || wm.length_squared() == 0.0 {
+            return None;
+        }
+        let mut wm = wm.normalize();
+        if wm.z() < 0.0 {
+            wm = -wm;
+        }
+        if dot(&wm, wi) * cos_theta(wi) < 0.0 || dot(&wm, wo) < 0.0 {
+            return None;
+        }
+        Some(wm)
+    }
+
+    fn f(&self, wo: &Vec3, wi: &Vec3, interface: &Interface) -> Vec3 {
+        let eta = interface.eta;
+        let Some(wm) = Self::half_vector(wo, wi, eta) else {
+            return Vec3::default();
+        };
+        let cos_theta_o = cos_theta(wo);
+        let cos_theta_i = cos_theta(wi);
+        let fresnel = interface.fresnel(dot(wo, &wm));
+
+        if cos_theta_i > 0.0 {
+            self.distrib.d(&wm) * self.distrib.g(wo, wi) * fresnel
+                / (4.0 * cos_theta_i * cos_theta_o).abs()
+        } else {
+            let denom = (dot(wi, &wm) + dot(wo, &wm) / eta).powi(2) * cos_theta_i * cos_theta_o;
+            let value = self.distrib.d(&wm)
+                * self.distrib.g(wo, wi)
+                * (dot(wi, &wm) * dot(wo, &wm) / denom).abs()
+                / (eta * eta);
+            (Vec3::new(1.0, 1.0, 1.0) - fresnel) * value
+        }
+    }
+
+    fn local_pdf(&self, wo: &Vec3, wi: &Vec3, interface: &Interface) -> f32 {
+        let eta = interface.eta;
+        let Some(wm) = Self::half_vector(wo, wi, eta) else {
+            return 0.0;
+        };
+        let r = interface.reflect_probability(dot(wo, &wm));
+
+        if cos_theta(wi) > 0.0 {
+            self.distrib.pdf(wo, &wm) / (4.0 * dot(wo, &wm).abs()) * r
+        } else {
+            self.refraction_density(wo, wi, &wm, eta) * (1.0 - r)
+        }
+    }
+
+    // Density of refracting into `wi` through a sampled microfacet, before choosing refraction
+    // over reflection.
+    fn refraction_density(&self, wo: &Vec3, wi: &Vec3, wm: &Vec3, eta: f32) -> f32 {
+        let denom = (dot(wi, wm) + dot(wo, wm) / eta).powi(2);
+        self.distrib.pdf(wo, wm) * dot(wi, wm).abs() / denom
+    }
+
+    // The refracted part of a rough interface alone, for materials that bring their own
+    // reflection. The sample always refracts, so its pdf leaves out the Fresnel choice.
+    pub(crate) fn sample_refraction(&self, r_in: &Ray, rec: &HitRecord) -> Option<Vec3> {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        if cos_theta(&wo) <= 0.0 {
+            return None;
+        }
+        let wm = self.distrib.sample_wm(&wo, (rand_f32(), rand_f32()));
+        let wi = refract_local(&wo, &wm, self.interface(r_in, rec).eta)?;
+        (cos_theta(&wi) < 0.0).then(|| uvw.transform(&wi))
+    }
+
+    pub(crate) fn eval_refraction(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        if cos_theta(&wi) >= 0.0 {
+            return Vec3::default();
+        }
+        self.f(&wo, &wi, &self.interface(r_in, rec))
+            * cos_theta(&wi).abs()
+            * self.transmittance(r_in, rec)
+    }
+
+    pub(crate) fn refraction_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        let eta = self.interface(r_in, rec).eta;
+        match Self::half_vector(&wo, &wi, eta) {
+            Some(wm) if cos_theta(&wi) < 0.0 => self.refraction_density(&wo, &wi, &wm, eta),
+            _ => 0.0,
+        }
+    }
+
+    fn sample_smooth(&self, wo: &Vec3, interface: &Interface) -> Option<(Vec3, Vec3, f32, Lobe)> {
+        let eta = interface.eta;
+        let fresnel = interface.fresnel(cos_theta(wo));
+        let r = interface.reflect_probability(cos_theta(wo));
+
+        if rand_f32() < r {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            return Some((wi, fresnel / r, r, Lobe::REFLECTION));
+        }
+
+        let wi = refract_local(wo, &Vec3::new(0.0, 0.0, 1.0), eta)?;
+        let weight = (Vec3::new(1.0, 1.0, 1.0) - fresnel) / ((1.0 - r) * eta * eta);
+        Some((wi, weight, 1.0 - r, Lobe::TRANSMISSION))
+    }
+
+    fn sample_rough(&self, wo: &Vec3, interface: &Interface) -> Option<(Vec3, Vec3, f32, Lobe)> {
+        let eta = interface.eta;
+        let wm = self.distrib.sample_wm(wo, (rand_f32(), rand_f32()));
+        let r = interface.reflect_probability(dot(wo, &wm));
+
+        let (wi, lobe) = if rand_f32() < r {
+            let wi = reflect_local(wo, &wm);
+            if cos_theta(&wi) <= 0.0 {
+                return None;
+            }
+            (wi, Lobe::REFLECTION)
+        } else {
+            let wi = refract_local(wo, &wm, eta)?;
+            if cos_theta(&wi) >= 0.0 {
+                return None;
+            }
+            (wi, Lobe::TRANSMISSION)
+        };
+
+        let pdf = self.local_pdf(wo, &wi, interface);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let weight = self.f(wo, &wi, interface) * (cos_theta(&wi).abs() / pdf);
+        Some((wi, weight, pdf, lobe))
+    }
+}
+
+impl Bsdf for RoughDielectric {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        if cos_theta(&wo) <= 0.0 {
+            return None;
+        }
+        let interface = self.interface(r_in, rec);
+
+        let (wi, weight, pdf, mut lobe) = if self.distrib.effectively_smooth() {
+            let (wi, weight, pdf, lobe) = self.sample_smooth(&wo, &interface)?;
+            (wi, weight, pdf, Lobe::SPECULAR | lobe)
+        } else {
+            let (wi, weight, pdf, lobe) = self.sample_rough(&wo, &interface)?;
+            (wi, weight, pdf, Lobe::GLOSSY | lobe)
+        };
+        // The sample only holds for the hero wavelength's index of refraction.
+        if self.refraction_index.is_dispersive() && r_in.wavelengths().is_some() {
+            lobe = lobe | Lobe::DISPERSIVE;
+        }
+
+        Some(BsdfSample {
+            direction: uvw.transform(&wi),
+            weight: weight * self.transmittance(r_in, rec),
+            pdf,
+            lobe,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        if self.distrib.effectively_smooth() {
+            return Vec3::default();
+        }
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+
+        self.f(&wo, &wi, &self.interface(r_in, rec))
+            * cos_theta(&wi).abs()
+            * self.transmittance(r_in, rec)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        if self.distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+
+        self.local_pdf(&wo, &wi, &self.interface(r_in, rec))
+    }
+}
+
+// Both sides of a dielectric surface as seen by an incoming ray.
+struct Interface {
+    eta: f32, // Index of refraction on the far side relative to the incident side
+    n_i: f32,
+    n_t: f32,
+    lambda: [f32; 3], // Wavelength of each colour channel
+    thin_film: Option<ThinFilm>,
+}
+
+impl Interface {
+    // Fresnel reflectance per channel, which only varies with the wavelength under a thin film.
+    fn fresnel(&self, cos_theta_i: f32) -> Vec3 {
+        match &self.thin_film {
+            Some(film) => {
+                let r =
+                    |lambda: f32| film.reflectance(cos_theta_i, self.n_i, self.n_t.into(), lambda);
+                Vec3::new(r(self.lambda[0]), r(self.lambda[1]), r(self.lambda[2]))
+            }
+            None => {
+                let r = fr_dielectric(cos_theta_i, self.eta);
+                Vec3::new(r, r, r)
+            }
+        }
+    }
+
+    // Probability of sampling reflection, the reflectance averaged over the channels.
+    fn reflect_probability(&self, cos_theta_i: f32) -> f32 {
+        let fresnel = self.fresnel(cos_theta_i);
+        (fresnel.x() + fresnel.y() + fresnel.z()) / 3.0
+    }
+}
+
+// Infinitely thin dielectric sheet, for window panes and soap bubbles. Light is either reflected
+// or passes straight through, accounting for the inter-reflection between both faces. A thin film
+// on the faces gives a bubble its iridescence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThinDielectric {
+    pub refraction_index: Ior,
+    pub thin_film: Option<ThinFilm>,
+}
+
+impl ThinDielectric {
+    pub fn new(refraction_index: impl Into<Ior>) -> Self {
+        ThinDielectric {
+            refraction_index: refraction_index.into(),
+            thin_film: None,
+        }
+    }
+
+    // Coat both faces with a thin film for iridescence.
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    // Reflectance of the whole sheet per channel, summing the bounces between its two faces.
+    fn reflectance(&self, r_in: &Ray, cos_theta: f32) -> Vec3 {
+        let n = self.refraction_index.for_ray(r_in);
+        let face = Interface {
+            eta: n,
+            n_i: 1.0,
+            n_t: n,
+            lambda: r_in.channel_wavelengths(),
+            thin_film: self.thin_film,
+        }
+        .fresnel(cos_theta);
+
+        let sheet = |r: f32| {
+            if r < 1.0 {
+                r + (1.0 - r) * (1.0 - r) * r / (1.0 - r * r)
+            } else {
+                1.0
+            }
+        };
+        Vec3::new(sheet(face.x()), sheet(face.y()), sheet(face.z()))
+    }
+}
+
+impl Bsdf for ThinDielectric {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let unit_direction = r_in.direction().normalize();
+        let cos_theta = dot(&-unit_direction, &rec.normal).abs();
+
+        let r = self.reflectance(r_in, cos_theta);
+        let t = Vec3::new(1.0, 1.0, 1.0) - r;
+        let p = (r.x() + r.y() + r.z()) / 3.0;
+
+        let (direction, weight, pdf, lobe) = if rand_f32() < p {
+            let direction = reflect(&unit_direction, &rec.normal);
+            (direction, r / p, p, Lobe::REFLECTION)
+        } else {
+            (unit_direction, t / (1.0 - p), 1.0 - p, Lobe::TRANSMISSION)
+        };
+
+        Some(BsdfSample {
+            direction,
+            weight,
+            pdf,
+            lobe: Lobe::SPECULAR | lobe,
+        })
+    }
+
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Vec3 {
+        Vec3::default()
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> f32 {
+        0.0
+    }
+}
+
+#[test]
+fn test_rough_sample_matches_eval() {
+    let glass = RoughDielectric::new(1.5, 0.5);
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -0.4));
+
+    for front_face in [true, false] {
+        let rec = HitRecord {
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            front_face,
+            ..Default::default()
+        };
+
+        for _ in 0..200 {
+            let Some(srec) = glass.sample(&r_in, &rec) else {
+                continue;
+            };
+            let pdf = glass.pdf(&r_in, &rec, &srec.direction);
+            let expected = glass.eval(&r_in, &rec, &srec.direction) / pdf;
+
+            assert!((pdf - srec.pdf).abs() <= 1e-3 * pdf);
+            assert!((expected - srec.weight).length() <= 1e-3 * expected.length());
+        }
+    }
+}
+
+#[test]
+fn test_thin_dielectric_film() {
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -0.4));
+    let cos_theta = 1.0 / 1.16_f32.sqrt();
+    let bare = ThinDielectric::new(1.5).reflectance(&r_in, cos_theta);
+
+    // A film of no thickness leaves the bare sheet, which reflects all channels alike.
+    let none = ThinDielectric::new(1.5)
+        .with_thin_film(ThinFilm::new(0.0, 1.33))
+        .reflectance(&r_in, cos_theta);
+    assert!((none - bare).length() < 1e-4);
+    assert!(bare.x() == bare.y() && bare.y() == bare.z());
+
+    // A soap film a few hundred nanometres thick colours the reflection.
+    let bubble = ThinDielectric::new(1.0)
+        .with_thin_film(ThinFilm::new(300.0, 1.33))
+        .reflectance(&r_in, cos_theta);
+    assert!((bubble.x() - bubble.z()).abs() > 0.01);
+    assert!(bubble.x() <= 1.0 && bubble.z() <= 1.0);
+}
@@ -0,0 +1,205 @@
+use std::f32::consts::PI;
+
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Lobe},
+    hittable::HitRecord,
+    microfacet::{cos_phi, cos_theta, sin2_theta, sin_phi},
+    ray::Ray,
+    util::rand_f32,
+    vec3::{random_cosine_direction, Vec3},
+};
+
+// Oren-Nayar rough diffuse reflection for clay and cloth-like surfaces. `sigma` is the standard
+// deviation of the microfacet slope angle in degrees; zero gives a Lambertian surface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrenNayar {
+    pub albedo: Vec3,
+    a: f32,
+    b: f32,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Vec3, sigma: f32) -> Self {
+        let sigma = sigma.to_radians();
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Vec3 {
+        let sin_theta_i = sin2_theta(wi).sqrt();
+        let sin_theta_o = sin2_theta(wo).sqrt();
+
+        // Compute the cosine term of the Oren-Nayar model.
+        let max_cos = if sin_theta_i > 1e-4 && sin_theta_o > 1e-4 {
+            (cos_phi(wi) * cos_phi(wo) + sin_phi(wi) * sin_phi(wo)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // Compute the sine and tangent terms of the Oren-Nayar model.
+        let (sin_alpha, tan_beta) = if cos_theta(wi).abs() > cos_theta(wo).abs() {
+            (sin_theta_o, sin_theta_i / cos_theta(wi).abs())
+        } else {
+            (sin_theta_i, sin_theta_o / cos_theta(wo).abs())
+        };
+
+        self.albedo * ((self.a + self.b * max_cos * sin_alpha * tan_beta) / PI)
+    }
+}
+
+impl Bsdf for OrenNayar {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = random_cosine_direction();
+        let pdf = cos_theta(&wi) / PI;
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            direction: uvw.transform(&wi),
+            weight: r_in.spectrum(&self.f(&wo, &wi)) * (cos_theta(&wi) / pdf),
+            pdf,
+            lobe: Lobe::DIFFUSE | Lobe::REFLECTION,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        if cos_theta(&wi) <= 0.0 {
+            return Vec3::default();
+        }
+        r_in.spectrum(&self.f(&wo, &wi)) * cos_theta(&wi)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        let uvw = rec.frame();
+        cos_theta(&uvw.to_local(&direction.normalize())).max(0.0) / PI
+    }
+}
+
+// Diffuse reflection plus diffuse transmission to the other side, for leaves and paper.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffuseTransmission {
+    pub reflectance: Vec3,
+    pub transmittance: Vec3,
+}
+
+impl DiffuseTransmission {
+    pub fn new(reflectance: Vec3, transmittance: Vec3) -> Self {
+        DiffuseTransmission {
+            reflectance,
+            transmittance,
+        }
+    }
+
+    // Probability of sampling reflection over transmission.
+    fn reflect_probability(&self) -> f32 {
+        let pr = max_component(&self.reflectance);
+        let pt = max_component(&self.transmittance);
+        if pr + pt == 0.0 {
+            return 0.5;
+        }
+        pr / (pr + pt)
+    }
+}
+
+impl Bsdf for DiffuseTransmission {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let uvw = rec.frame();
+        let pr = self.reflect_probability();
+        let mut wi = random_cosine_direction();
+
+        let (color, pdf, lobe) = if rand_f32() < pr {
+            (self.reflectance, pr, Lobe::REFLECTION)
+        } else {
+            wi = -wi;
+            (self.transmittance, 1.0 - pr, Lobe::TRANSMISSION)
+        };
+        let pdf = pdf * cos_theta(&wi).abs() / PI;
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            direction: uvw.transform(&wi),
+            weight: r_in.spectrum(&color) * (cos_theta(&wi).abs() / (PI * pdf)),
+            pdf,
+            lobe: Lobe::DIFFUSE | lobe,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        let uvw = rec.frame();
+        let cos_theta_i = cos_theta(&uvw.to_local(&direction.normalize()));
+        let color = if cos_theta_i > 0.0 {
+            self.reflectance
+        } else {
+            self.transmittance
+        };
+        r_in.spectrum(&color) * (cos_theta_i.abs() / PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        let uvw = rec.frame();
+        let cos_theta_i = cos_theta(&uvw.to_local(&direction.normalize()));
+        let pr = self.reflect_probability();
+        let p = if cos_theta_i > 0.0 { pr } else { 1.0 - pr };
+        p * cos_theta_i.abs() / PI
+    }
+}
+
+fn max_component(v: &Vec3) -> f32 {
+    v.x().max(v.y()).max(v.z())
+}
+
+#[cfg(test)]
+fn white_furnace(bsdf: &impl Bsdf, r_in: &Ray) -> Vec3 {
+    // Average the sample weights under uniform white illumination, which gives the fraction of
+    // energy the BSDF scatters.
+    let rec = HitRecord {
+        normal: Vec3::new(0.0, 1.0, 0.0),
+        front_face: true,
+        ..Default::default()
+    };
+    let n = 100_000;
+    let mut sum = Vec3::default();
+    for _ in 0..n {
+        if let Some(srec) = bsdf.sample(r_in, &rec) {
+            sum += srec.weight;
+        }
+    }
+    sum / n
+}
+
+#[test]
+fn test_oren_nayar_white_furnace() {
+    let white = Vec3::new(1.0, 1.0, 1.0);
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.3, -1.0, -0.6));
+
+    // With no roughness all energy is reflected, as for a Lambertian surface.
+    let smooth = white_furnace(&OrenNayar::new(white, 0.0), &r_in);
+    assert!((smooth.x() - 1.0).abs() < 1e-3);
+
+    // Rough surfaces must never reflect more energy than they receive.
+    for sigma in [10.0, 30.0, 60.0] {
+        let rough = white_furnace(&OrenNayar::new(white, sigma), &r_in);
+        assert!(rough.x() <= 1.01 && rough.x() > 0.5);
+    }
+}
+
+#[test]
+fn test_diffuse_transmission_white_furnace() {
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.3, -1.0, -0.6));
+    let leaf = DiffuseTransmission::new(Vec3::new(0.3, 0.6, 0.4), Vec3::new(0.7, 0.4, 0.6));
+
+    let albedo = white_furnace(&leaf, &r_in);
+    assert!((albedo - Vec3::new(1.0, 1.0, 1.0)).length() < 0.02);
+}
@@ -0,0 +1,130 @@
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_sign_loss)]
+#![allow(clippy::cast_precision_loss)]
+use std::{f32::consts::PI, io, path::Path};
+
+use crate::{color::luminance, image::Image, sampling::Distribution2D, util::rand_f32, Vec3};
+
+// Equirectangular environment map lighting the scene from infinitely far away. The top row of the
+// image is straight up (+y), the centre column looks down -z and the left quarter looks down -x.
+pub struct EnvironmentMap {
+    image: Image,
+    rotation: f32,  // Radians about the vertical axis
+    intensity: f32, // Scale applied to the image's radiance
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image) -> Self {
+        assert!(image.width() > 0 && image.height() > 0);
+        let (width, height) = (image.width(), image.height());
+
+        // Sample pixels by brightness, scaled by the solid angle their row covers on the sphere.
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+            func.extend((0..width).map(|x| luminance(&image.pixel(x, y)).max(0.0) * sin_theta));
+        }
+
+        EnvironmentMap {
+            distribution: Distribution2D::new(&func, width),
+            image,
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    // Load a `.hdr` or `.exr` file, or any other format `Image::load` reads.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(Image::load(path)?))
+    }
+
+    // Turns the map clockwise about the vertical axis, seen from above.
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    // Image coordinates in [0, 1)² of a direction, with v pointing down.
+    fn uv(&self, direction: &Vec3) -> (f32, f32) {
+        let d = direction.normalize();
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        let phi = (-d.x()).atan2(d.z()) - self.rotation;
+
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        (u.min(1.0 - f32::EPSILON), theta / PI)
+    }
+
+    fn direction(&self, u: f32, v: f32) -> Vec3 {
+        let theta = v * PI;
+        let phi = 2.0 * PI * u + self.rotation;
+        let sin_theta = theta.sin();
+
+        Vec3::new(-sin_theta * phi.sin(), theta.cos(), sin_theta * phi.cos())
+    }
+
+    // Radiance arriving from a direction, in RGB.
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let (u, v) = self.uv(direction);
+        let x = (u * self.image.width() as f32) as usize;
+        let y = (v * self.image.height() as f32) as usize;
+        self.intensity * self.image.pixel(x, y)
+    }
+
+    // Picks a direction towards the map in proportion to its brightness, returning it with its
+    // solid angle density.
+    pub fn sample(&self) -> (Vec3, f32) {
+        let ((u, v), pdf) = self.distribution.sample((rand_f32(), rand_f32()));
+        let direction = self.direction(u, v);
+
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return (direction, 0.0);
+        }
+        // The map covers 2π by π radians, squeezed together by sin θ towards the poles.
+        (direction, pdf / (2.0 * PI * PI * sin_theta))
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f32 {
+        let (u, v) = self.uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[test]
+fn test_environment_map() {
+    // A dark map with a single bright pixel in the middle row, a quarter of the way across.
+    let mut pixels = vec![Vec3::new(0.01, 0.01, 0.01); 8 * 4];
+    pixels[8 * 2 + 2] = Vec3::new(100.0, 100.0, 100.0);
+    let map = EnvironmentMap::new(Image::new(8, 4, pixels))
+        .with_rotation(90.0)
+        .with_intensity(2.0);
+
+    // Samples landing right on a pixel edge may round into the neighbouring pixel on the way back.
+    let (mut bright, mut matching) = (0, 0);
+    for _ in 0..1000 {
+        let (direction, pdf) = map.sample();
+        assert!(pdf > 0.0);
+        if (map.pdf(&direction) - pdf).abs() < 1e-3 * pdf {
+            matching += 1;
+        }
+        if map.radiance(&direction).x() > 100.0 {
+            bright += 1;
+        }
+    }
+    assert!(bright > 950 && matching > 990);
+
+    // Rotating by 90 degrees turns the -x quarter of the image towards -z.
+    let centre = map.direction(0.25 + 0.5 / 8.0, 2.5 / 4.0);
+    assert!(centre.z() < -0.8);
+    assert_eq!(map.radiance(&centre), Vec3::new(200.0, 200.0, 200.0));
+}
@@ -0,0 +1,112 @@
+#![allow(clippy::cast_precision_loss)]
+use crate::{color::luminance, Vec3};
+
+// Mid grey, where auto exposure puts the image's average.
+const MIDDLE_GREY: f32 = 0.18;
+
+// How the camera turns the radiance it gathers into pixel values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Exposure {
+    // Radiance is the pixel value.
+    #[default]
+    Unit,
+    // A real camera's settings, for scenes lit in luminance units (cd/m²). Sensitivity is ISO,
+    // the shutter time is in seconds, and without an f-number the camera works one out from its
+    // lens or defocus disk.
+    Physical {
+        iso: f32,
+        shutter: f32,
+        f_number: Option<f32>,
+    },
+    // Scales the image so its log-average luminance comes out at `key` of full white, 0.18 for mid
+    // grey.
+    Auto {
+        key: f32,
+    },
+}
+
+impl Exposure {
+    pub fn physical(iso: f32, shutter: f32, f_number: f32) -> Self {
+        Exposure::Physical {
+            iso,
+            shutter,
+            f_number: Some(f_number),
+        }
+    }
+
+    pub fn auto() -> Self {
+        Exposure::Auto { key: MIDDLE_GREY }
+    }
+
+    // Factor from the rendered radiance to pixel values. `f_number` stands in when physical
+    // settings leave theirs out, and without either there is none.
+    pub fn scale(&self, f_number: Option<f32>, image: &[Vec3]) -> Option<f32> {
+        match *self {
+            Exposure::Unit => Some(1.0),
+            Exposure::Physical {
+                iso,
+                shutter,
+                f_number: settings,
+            } => {
+                let f_number = settings.or(f_number)?;
+                // The luminance that just saturates the sensor, from ISO 12232's saturation based
+                // speed with a lens letting through 65% of the light.
+                let saturation = 1.2 * 2.0_f32.powf(exposure_value(iso, shutter, f_number));
+                Some(1.0 / saturation)
+            }
+            Exposure::Auto { key } => {
+                let average = log_average_luminance(image);
+                Some(if average > 0.0 { key / average } else { 1.0 })
+            }
+        }
+    }
+}
+
+// Exposure value at ISO 100 of camera settings, in stops: 0 is one second at f/1.
+pub fn exposure_value(iso: f32, shutter: f32, f_number: f32) -> f32 {
+    (f_number * f_number / shutter * 100.0 / iso).log2()
+}
+
+// Geometric mean of the luminance of an image's pixels, less swayed by a few bright highlights
+// than the plain average.
+pub fn log_average_luminance(image: &[Vec3]) -> f32 {
+    if image.is_empty() {
+        return 0.0;
+    }
+    let sum: f32 = image
+        .iter()
+        .map(|pixel| (1e-4 + luminance(pixel).max(0.0)).ln())
+        .sum();
+    (sum / image.len() as f32).exp()
+}
+
+#[test]
+fn test_exposure() {
+    // The sunny 16 rule: f/16 at a shutter of one over the ISO is about EV 15.
+    assert!((exposure_value(100.0, 1.0 / 125.0, 16.0) - 15.0).abs() < 0.05);
+    assert!((exposure_value(400.0, 1.0 / 500.0, 16.0) - 15.0).abs() < 0.05);
+
+    // Doubling the shutter time or the ISO doubles the exposure, and so does one stop wider.
+    let base = Exposure::physical(100.0, 0.01, 8.0)
+        .scale(None, &[])
+        .unwrap();
+    let longer = Exposure::physical(100.0, 0.02, 8.0)
+        .scale(None, &[])
+        .unwrap();
+    let wider = Exposure::physical(100.0, 0.01, 8.0 / 2.0_f32.sqrt())
+        .scale(None, &[])
+        .unwrap();
+    assert!((longer / base - 2.0).abs() < 1e-4 && (wider / base - 2.0).abs() < 1e-4);
+    let derived = Exposure::Physical {
+        iso: 100.0,
+        shutter: 0.01,
+        f_number: None,
+    };
+    assert_eq!(derived.scale(Some(8.0), &[]), Some(base));
+    assert_eq!(derived.scale(None, &[]), None);
+
+    // Auto exposure brings any uniform image to mid grey.
+    let image = vec![Vec3::new(40.0, 40.0, 40.0); 16];
+    let scale = Exposure::auto().scale(None, &image).unwrap();
+    assert!((luminance(&(scale * image[0])) - MIDDLE_GREY).abs() < 1e-3);
+}
@@ -0,0 +1,222 @@
+use std::{
+    f32::consts::PI,
+    ops::{Add, Div, Mul, Sub},
+};
+
+use crate::Vec3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Self {
+        Complex { re, im }
+    }
+
+    // Squared magnitude.
+    pub fn norm(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn sqrt(&self) -> Complex {
+        let n = self.norm().sqrt();
+        if n == 0.0 {
+            return Complex::default();
+        }
+        let t1 = f32::sqrt(0.5 * (n + self.re.abs()));
+        let t2 = 0.5 * self.im / t1;
+
+        if self.re >= 0.0 {
+            Complex::new(t1, t2)
+        } else {
+            Complex::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+
+    // e^(i z), the phase factor of a wave travelling a complex optical distance.
+    pub fn exp_i(&self) -> Complex {
+        let magnitude = f32::exp(-self.im);
+        Complex::new(magnitude * self.re.cos(), magnitude * self.re.sin())
+    }
+}
+
+impl From<f32> for Complex {
+    fn from(re: f32) -> Self {
+        Complex::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let scale = 1.0 / rhs.norm();
+        Complex::new(
+            scale * (self.re * rhs.re + self.im * rhs.im),
+            scale * (self.im * rhs.re - self.re * rhs.im),
+        )
+    }
+}
+
+// Unpolarized Fresnel reflectance of a dielectric interface, where `eta` is the index of refraction
+// on the far side of the interface relative to the side `cos_theta_i` is measured from.
+pub fn fr_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+    // Potentially flip the interface orientation for the Fresnel equations.
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+    }
+
+    // Compute cos_theta_t using Snell's law.
+    let sin2_theta_i = 1.0 - cos_theta_i * cos_theta_i;
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = f32::sqrt(1.0 - sin2_theta_t);
+
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+// Unpolarized Fresnel reflectance of a conductor with complex index of refraction `eta`.
+pub fn fr_complex(cos_theta_i: f32, eta: Complex) -> f32 {
+    let cos_theta_i = Complex::from(cos_theta_i.clamp(0.0, 1.0));
+    let sin2_theta_i = Complex::from(1.0) - cos_theta_i * cos_theta_i;
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    let cos_theta_t = (Complex::from(1.0) - sin2_theta_t).sqrt();
+
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parl.norm() + r_perp.norm()) / 2.0
+}
+
+// Per channel conductor Fresnel reflectance for RGB indices of refraction.
+pub fn fr_complex_rgb(cos_theta_i: f32, eta: &Vec3, k: &Vec3) -> Vec3 {
+    Vec3::new(
+        fr_complex(cos_theta_i, Complex::new(eta.x(), k.x())),
+        fr_complex(cos_theta_i, Complex::new(eta.y(), k.y())),
+        fr_complex(cos_theta_i, Complex::new(eta.z(), k.z())),
+    )
+}
+
+// A thin transparent film coating a surface, such as a soap bubble or an anti-reflective lens
+// coating. Light reflected off both sides of the film interferes, which tints the Fresnel
+// reflectance depending on the wavelength and angle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThinFilm {
+    pub thickness: f32, // Film thickness in nanometers
+    pub ior: f32,       // Index of refraction of the film
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, ior: f32) -> Self {
+        ThinFilm { thickness, ior }
+    }
+
+    // Unpolarized reflectance at wavelength `lambda` in nanometers, for light arriving from a
+    // medium with index `n_i` onto the film over a substrate with complex index `n_t`. Sums the
+    // multiple reflections inside the film with the Airy formula.
+    pub fn reflectance(&self, cos_theta_i: f32, n_i: f32, n_t: Complex, lambda: f32) -> f32 {
+        let one = Complex::from(1.0);
+        let cos_i = Complex::from(cos_theta_i.clamp(0.0, 1.0));
+        let sin2_i = one - cos_i * cos_i;
+        let (n_i, n_f) = (Complex::from(n_i), Complex::from(self.ior));
+
+        // Snell's law into the film and the substrate. Complex cosines cover total internal
+        // reflection and absorbing substrates.
+        let cos_f = (one - sin2_i * (n_i * n_i) / (n_f * n_f)).sqrt();
+        let cos_t = (one - sin2_i * (n_i * n_i) / (n_t * n_t)).sqrt();
+
+        let r_perp = |n1: Complex, cos1: Complex, n2: Complex, cos2: Complex| {
+            (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2)
+        };
+        let r_parl = |n1: Complex, cos1: Complex, n2: Complex, cos2: Complex| {
+            (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2)
+        };
+
+        // Phase difference between successive reflections off the bottom of the film.
+        let phase = (Complex::from(4.0 * PI * self.thickness / lambda) * n_f * cos_f).exp_i();
+        let airy =
+            |r12: Complex, r23: Complex| ((r12 + r23 * phase) / (one + r12 * r23 * phase)).norm();
+
+        let perp = airy(
+            r_perp(n_i, cos_i, n_f, cos_f),
+            r_perp(n_f, cos_f, n_t, cos_t),
+        );
+        let parl = airy(
+            r_parl(n_i, cos_i, n_f, cos_f),
+            r_parl(n_f, cos_f, n_t, cos_t),
+        );
+        ((perp + parl) / 2.0).clamp(0.0, 1.0)
+    }
+}
+
+#[test]
+fn test_thin_film() {
+    // A film of zero thickness leaves the bare interface.
+    let film = ThinFilm::new(0.0, 1.33);
+    for cos in [1.0, 0.7, 0.2] {
+        let bare = fr_dielectric(cos, 1.5);
+        let coated = film.reflectance(cos, 1.0, Complex::from(1.5), 550.0);
+        assert!((bare - coated).abs() < 1e-4);
+    }
+
+    // A quarter wave anti-reflective coating with the geometric mean index cancels reflection.
+    let n = 1.5_f32.sqrt();
+    let coating = ThinFilm::new(550.0 / (4.0 * n), n);
+    assert!(coating.reflectance(1.0, 1.0, Complex::from(1.5), 550.0) < 1e-4);
+}
+
+#[test]
+fn test_fr_complex_normal_incidence() {
+    // With no absorption the reflectance at normal incidence is ((n - 1) / (n + 1))^2.
+    let n: f32 = 1.5;
+    let expected = ((n - 1.0) / (n + 1.0)).powi(2);
+    assert!((fr_complex(1.0, Complex::from(n)) - expected).abs() < 1e-5);
+    assert!((fr_dielectric(1.0, n) - expected).abs() < 1e-5);
+
+    // Past the critical angle light inside the denser medium is totally reflected.
+    assert_eq!(fr_dielectric(0.2, 1.0 / n), 1.0);
+
+    // Gold reflects far more red than blue light.
+    let gold = fr_complex_rgb(
+        1.0,
+        &Vec3::new(0.143, 0.374, 1.442),
+        &Vec3::new(3.983, 2.385, 1.603),
+    );
+    assert!(gold.x() > 0.9 && gold.z() < 0.5);
+}
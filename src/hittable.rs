@@ -1,15 +1,59 @@
-use crate::{material::Material, ray::Ray, vec3::dot, Vec3};
+use crate::{
+    aabb::Aabb,
+    hittable_list::HittableObject,
+    interval::Interval,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    vec3::{cross, dot},
+    Vec3,
+};
 
-#[derive(Clone, Copy, Default)]
-pub struct HitRecord {
+// Material of records that haven't hit anything yet.
+static NO_MATERIAL: Material = Material::Lambartian {
+    albedo: Vec3::new(0.0, 0.0, 0.0),
+};
+
+// Details of a ray hit. The material is borrowed from the object hit, so keeping the closest of
+// many candidate hits doesn't copy materials around.
+#[derive(Clone, Copy)]
+pub struct HitRecord<'a> {
     pub front_face: bool,
-    pub mat: Material,
-    pub normal: Vec3,
+    pub mat: &'a Material,
+    pub normal: Vec3,           // Shading normal, facing the incoming ray
+    pub geometric_normal: Vec3, // True surface normal, facing the incoming ray
     pub p: Vec3,
     pub t: f32,
+    pub u: f32, // Surface coordinates of the hit point
+    pub v: f32,
+    pub dpdu: Vec3, // Partial derivatives of the hit point along the surface coordinates
+    pub dpdv: Vec3,
+    pub tangent: Vec3, // Shading frame around the shading normal, following dpdu
+    pub bitangent: Vec3,
+    pub object: Option<&'a HittableObject>, // Primitive hit, for recognising area lights
+}
+
+impl Default for HitRecord<'_> {
+    fn default() -> Self {
+        HitRecord {
+            front_face: false,
+            mat: &NO_MATERIAL,
+            normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            p: Vec3::default(),
+            t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            tangent: Vec3::default(),
+            bitangent: Vec3::default(),
+            object: None,
+        }
+    }
 }
 
-impl HitRecord {
+impl HitRecord<'_> {
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         // Sets the hit record normal vector.
         // NOTE: the parameter 'outward_normal' is assumed to have unit_length
@@ -20,6 +64,53 @@ impl HitRecord {
             outward_normal
         } else {
             -outward_normal
+        };
+        self.geometric_normal = self.normal;
+    }
+
+    // Sets the surface derivatives and the shading frame after `set_face_normal`. The shading
+    // normal points outwards like the geometric one and is flipped the same way.
+    pub fn set_shading(&mut self, dpdu: Vec3, dpdv: Vec3, shading_normal: Vec3) {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        let n = shading_normal.normalize();
+        self.normal = if self.front_face { n } else { -n };
+
+        // Gram-Schmidt dpdu against the shading normal, falling back to an arbitrary tangent when
+        // the derivatives are degenerate, like at the poles of a sphere.
+        let tangent = dpdu - n * dot(&n, &dpdu);
+        self.tangent = if tangent.length_squared() > 1e-12 {
+            tangent.normalize()
+        } else {
+            *Onb::new(&n).u()
+        };
+        self.bitangent = cross(&n, &self.tangent);
+    }
+
+    // Orthonormal basis around the shading normal for evaluating materials.
+    pub fn frame(&self) -> Onb {
+        if self.tangent.length_squared() == 0.0 {
+            return Onb::new(&self.normal);
         }
+        Onb::from_normal_tangent(&self.normal, &self.tangent)
+    }
+}
+
+/// Anything a ray can intersect. Implement this to add primitives of your own and wrap them in
+/// `HittableObject::Custom` to place them in a `HittableList` or `BvhNode`.
+pub trait Hittable: Send + Sync {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool;
+
+    fn bounding_box(&self) -> Aabb;
+
+    /// Solid angle density of choosing `direction` from `origin` with `random`. Objects that
+    /// can't be sampled as lights return zero.
+    fn pdf_value(&self, _origin: &Vec3, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    /// Returns a direction from `origin` towards a random point on the object.
+    fn random(&self, _origin: &Vec3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
     }
 }
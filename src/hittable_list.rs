@@ -1,64 +1,159 @@
-use crate::{hittable::HitRecord, interval::Interval, ray::Ray, sphere::Sphere, vec3::dot};
+use std::{f32::consts::PI, sync::Arc};
 
-#[derive(Default)]
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    masked::Masked,
+    material::Material,
+    mesh::Triangle,
+    quad::Quad,
+    ray::Ray,
+    sphere::Sphere,
+    texture::Texture,
+    util::rand_index,
+    Vec3,
+};
+
+// See `HittableObject::key`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ObjectKey {
+    Sphere([u32; 4]),
+    Quad([[u32; 3]; 3]),
+    Triangle((usize, usize)),
+    Custom(usize),
+}
+
+#[derive(Default, Clone)]
 pub struct HittableList {
     pub objects: Vec<HittableObject>,
 }
 
+// Built-in primitives are dispatched statically; anything else goes through `Custom`.
 #[derive(Clone)]
 pub enum HittableObject {
     Sphere(Sphere),
+    Quad(Quad),
+    Triangle(Triangle),
+    Bvh(Arc<BvhNode>),
+    Masked(Arc<Masked>),
+    Custom(Arc<dyn Hittable>),
 }
 
-impl HittableObject {
-    pub fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+impl Hittable for HittableObject {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        let hit = match self {
+            HittableObject::Sphere(sphere) => sphere.hit(r, ray_t, rec),
+            HittableObject::Quad(quad) => quad.hit(r, ray_t, rec),
+            HittableObject::Triangle(triangle) => triangle.hit(r, ray_t, rec),
+            HittableObject::Bvh(bvh) => return bvh.hit(r, ray_t, rec),
+            HittableObject::Masked(masked) => return masked.hit(r, ray_t, rec),
+            HittableObject::Custom(object) => object.hit(r, ray_t, rec),
+        };
+        // Aggregates leave the record to the primitive they hit.
+        if hit {
+            rec.object = Some(self);
+        }
+        hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
         match self {
-            HittableObject::Sphere(sphere) => Self::sphere_hit(sphere, r, ray_t, rec),
+            HittableObject::Sphere(sphere) => sphere.bounding_box(),
+            HittableObject::Quad(quad) => quad.bounding_box(),
+            HittableObject::Triangle(triangle) => triangle.bounding_box(),
+            HittableObject::Bvh(bvh) => bvh.bounding_box(),
+            HittableObject::Masked(masked) => masked.bounding_box(),
+            HittableObject::Custom(object) => object.bounding_box(),
         }
     }
 
-    fn sphere_hit(sphere: &Sphere, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
-        let oc = sphere.center - *r.origin();
-        let a = r.direction().length_squared();
-        let h = dot(r.direction(), &oc);
-        let c = oc.length_squared() - sphere.radius * sphere.radius;
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        match self {
+            HittableObject::Sphere(sphere) => sphere.pdf_value(origin, direction),
+            HittableObject::Quad(quad) => quad.pdf_value(origin, direction),
+            HittableObject::Triangle(triangle) => triangle.pdf_value(origin, direction),
+            HittableObject::Bvh(bvh) => bvh.pdf_value(origin, direction),
+            HittableObject::Masked(masked) => masked.pdf_value(origin, direction),
+            HittableObject::Custom(object) => object.pdf_value(origin, direction),
+        }
+    }
 
-        let discriminant = h * h - a * c;
-        if discriminant < 0.0 {
-            return false;
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        match self {
+            HittableObject::Sphere(sphere) => sphere.random(origin),
+            HittableObject::Quad(quad) => quad.random(origin),
+            HittableObject::Triangle(triangle) => triangle.random(origin),
+            HittableObject::Bvh(bvh) => bvh.random(origin),
+            HittableObject::Masked(masked) => masked.random(origin),
+            HittableObject::Custom(object) => object.random(origin),
         }
+    }
+}
 
-        let sqrtd = discriminant.sqrt();
+impl HittableObject {
+    // Cut away parts of the object with an alpha texture.
+    pub fn with_alpha(self, alpha: impl Into<Texture>) -> HittableObject {
+        HittableObject::Masked(Arc::new(Masked {
+            object: self,
+            alpha: alpha.into(),
+        }))
+    }
 
-        // Find the nearest root that lies in the acceptable range.
-        let mut root = (h - sqrtd) / a;
-        if !ray_t.surrounds(root) {
-            root = (h + sqrtd) / a;
+    // Surface area of a primitive, or zero for aggregates and custom objects.
+    pub fn area(&self) -> f32 {
+        match self {
+            HittableObject::Sphere(sphere) => 4.0 * PI * sphere.radius * sphere.radius,
+            HittableObject::Quad(quad) => quad.area(),
+            HittableObject::Triangle(triangle) => triangle.area(),
+            HittableObject::Masked(masked) => masked.object.area(),
+            HittableObject::Bvh(_) | HittableObject::Custom(_) => 0.0,
+        }
+    }
 
-            if !ray_t.surrounds(root) {
-                return false;
+    // Identity of a primitive or custom object that holds across clones, or None for the BVH.
+    pub(crate) fn key(&self) -> Option<ObjectKey> {
+        let bits = |v: &Vec3| [v.x().to_bits(), v.y().to_bits(), v.z().to_bits()];
+        match self {
+            HittableObject::Sphere(sphere) => {
+                let [x, y, z] = bits(&sphere.center);
+                Some(ObjectKey::Sphere([x, y, z, sphere.radius.to_bits()]))
+            }
+            HittableObject::Quad(quad) => Some(ObjectKey::Quad([
+                bits(&quad.q),
+                bits(&quad.u),
+                bits(&quad.v),
+            ])),
+            HittableObject::Triangle(triangle) => Some(ObjectKey::Triangle(triangle.id())),
+            HittableObject::Masked(masked) => masked.object.key(),
+            HittableObject::Custom(object) => {
+                Some(ObjectKey::Custom(Arc::as_ptr(object).cast::<()>() as usize))
             }
+            HittableObject::Bvh(_) => None,
         }
+    }
 
-        rec.t = root;
-        rec.p = r.at(rec.t);
-        let outward_normal = (rec.p - sphere.center) / sphere.radius;
-        rec.set_face_normal(r, outward_normal);
-        rec.mat = sphere.mat;
-        true
+    // Material of a primitive, or None for aggregates and custom objects.
+    pub fn material(&self) -> Option<&Material> {
+        match self {
+            HittableObject::Sphere(sphere) => Some(&sphere.mat),
+            HittableObject::Quad(quad) => Some(&quad.mat),
+            HittableObject::Triangle(triangle) => Some(triangle.material()),
+            HittableObject::Masked(masked) => masked.object.material(),
+            HittableObject::Bvh(_) | HittableObject::Custom(_) => None,
+        }
     }
 }
+
 impl HittableList {
     pub fn add(&mut self, object: HittableObject) {
         self.objects.push(object);
     }
+}
 
-    pub fn hit(
-        &self,
-        r: &crate::ray::Ray,
-        ray_t: &Interval,
-        rec: &mut crate::hittable::HitRecord,
-    ) -> bool {
+impl Hittable for HittableList {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
         let mut temp_record = HitRecord::default();
 
         let mut hit_anything = false;
@@ -77,4 +172,27 @@ impl HittableList {
         }
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::EMPTY, |bbox, object| {
+            Aabb::surrounding(&bbox, &object.bounding_box())
+        })
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let weight = 1.0 / self.objects.len() as f32;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        // Any direction will do for an empty list, whose pdf is zero everywhere.
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        self.objects[rand_index(self.objects.len())].random(origin)
+    }
 }
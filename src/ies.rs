@@ -0,0 +1,180 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+// Candela distribution of a luminaire from an IES LM-63 photometric file. Only type C photometry,
+// the kind used for architectural fixtures, is supported. Vertical angles are measured from the
+// nadir, straight down out of the fixture, and horizontal angles around it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IesProfile {
+    vertical: Vec<f32>,     // Degrees, ascending
+    horizontal: Vec<f32>,   // Degrees, ascending
+    candela: Vec<Vec<f32>>, // One row of vertical samples per horizontal angle
+}
+
+impl IesProfile {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn parse(text: &str) -> io::Result<Self> {
+        // Keywords come before the TILT line and the numeric data after it.
+        let tilt_at = text
+            .find("TILT=")
+            .ok_or_else(|| invalid("IES file without a TILT line"))?;
+        let (tilt_line, data) = text[tilt_at..]
+            .split_once('\n')
+            .unwrap_or((&text[tilt_at..], ""));
+        let mut numbers = data
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<f32>()
+                    .map_err(|_| invalid("malformed IES number"))
+            });
+        let mut next = || numbers.next().unwrap_or(Err(invalid("truncated IES data")));
+
+        match tilt_line.trim() {
+            "TILT=NONE" => {}
+            "TILT=INCLUDE" => {
+                // Lamp to luminaire geometry, then pairs of angles and multipliers we don't use.
+                next()?;
+                let pairs = next()? as usize;
+                for _ in 0..2 * pairs {
+                    next()?;
+                }
+            }
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::Unsupported,
+                    "IES tilt data in separate files is not supported",
+                ))
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        for _ in 0..4 {
+            next()?; // Units and luminous opening dimensions
+        }
+        let ballast_factor = next()?;
+        for _ in 0..2 {
+            next()?; // Reserved field and input watts
+        }
+
+        if photometric_type != 1.0 {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "only type C IES photometry is supported",
+            ));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid("IES file without angles"));
+        }
+
+        let vertical = (0..vertical_count)
+            .map(|_| next())
+            .collect::<io::Result<_>>()?;
+        let horizontal = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<io::Result<_>>()?;
+        let mut candela = Vec::with_capacity(horizontal_count);
+        for _ in 0..horizontal_count {
+            let row = (0..vertical_count)
+                .map(|_| Ok(next()? * multiplier * ballast_factor))
+                .collect::<io::Result<_>>()?;
+            candela.push(row);
+        }
+
+        Ok(IesProfile {
+            vertical,
+            horizontal,
+            candela,
+        })
+    }
+
+    pub fn max_candela(&self) -> f32 {
+        self.candela
+            .iter()
+            .flatten()
+            .fold(0.0, |max, &c| c.max(max))
+    }
+
+    // Luminous intensity in candela at `theta` degrees from the nadir and `phi` degrees around it,
+    // interpolated between the measured angles.
+    pub fn candela(&self, theta: f32, phi: f32) -> f32 {
+        let (v_min, v_max) = (self.vertical[0], self.vertical[self.vertical.len() - 1]);
+        if theta < v_min || theta > v_max {
+            return 0.0;
+        }
+
+        // Fold the angle around the nadir into the range the file covers, by its symmetry.
+        let last = self.horizontal[self.horizontal.len() - 1];
+        let mut phi = phi.rem_euclid(360.0);
+        if last <= 180.0 && phi > 180.0 {
+            phi = 360.0 - phi;
+        }
+        if last <= 90.0 && phi > 90.0 {
+            phi = 180.0 - phi;
+        }
+
+        let (h, h_t) = lerp_index(&self.horizontal, phi);
+        let (v, v_t) = lerp_index(&self.vertical, theta);
+        let at = |h: usize| {
+            let row = &self.candela[h];
+            row[v] + v_t * (row[(v + 1).min(row.len() - 1)] - row[v])
+        };
+        let next_h = (h + 1).min(self.horizontal.len() - 1);
+        at(h) + h_t * (at(next_h) - at(h))
+    }
+}
+
+// Index of the segment of sorted `angles` holding `x`, and how far along it `x` lies.
+fn lerp_index(angles: &[f32], x: f32) -> (usize, f32) {
+    let i = angles.partition_point(|&a| a <= x).saturating_sub(1);
+    if i + 1 >= angles.len() {
+        return (i, 0.0);
+    }
+    let width = angles[i + 1] - angles[i];
+    let t = if width > 0.0 {
+        (x - angles[i]) / width
+    } else {
+        0.0
+    };
+    (i, t.clamp(0.0, 1.0))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+#[test]
+fn test_ies_profile() {
+    // Rotationally symmetric downlight, doubled by the multiplier.
+    let symmetric = IesProfile::parse(
+        "IESNA:LM-63-2002\n[TEST] downlight\nTILT=NONE\n1 1000 2 3 1 1 2 0 0 0\n1.0 1 10\n\
+         0 45 90\n0\n100 50 0\n",
+    )
+    .unwrap();
+    assert_eq!(symmetric.candela(0.0, 123.0), 200.0);
+    assert_eq!(symmetric.candela(22.5, 0.0), 150.0);
+    assert_eq!(symmetric.candela(120.0, 0.0), 0.0);
+
+    // Quadrant symmetry mirrors the measured quarter around the fixture.
+    let quadrant = IesProfile::parse(
+        "TILT=INCLUDE\n1\n2\n0 90\n1 1\n1 -1 1 2 2 1 2 0 0 0\n1.0 1 10\n\
+         0, 90\n0, 90\n10, 10\n40, 40\n",
+    )
+    .unwrap();
+    assert_eq!(quadrant.candela(0.0, 45.0), 25.0);
+    assert_eq!(quadrant.candela(0.0, 270.0), 40.0);
+    assert_eq!(quadrant.candela(0.0, 180.0), 10.0);
+}
@@ -0,0 +1,592 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{color::gamma_to_linear, Vec3};
+
+// Largest image the loaders accept, so a corrupt header can't ask for an enormous allocation.
+const MAX_PIXELS: usize = 1 << 28;
+
+// Image with linear RGB pixels, stored row by row from the top left.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // Load an image, picking the format from the extension: Radiance `.hdr` and OpenEXR `.exr`
+    // for high dynamic range images, and binary (P6) or plain text (P3) PPM otherwise.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Image> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("hdr") => Self::from_hdr(&bytes),
+            Some("exr") => Self::from_exr(&bytes),
+            _ => Self::from_ppm(&bytes),
+        }
+    }
+
+    // Load a PPM holding data rather than colours, such as a normal or height map, so the values
+    // are kept as they are instead of being decoded from gamma.
+    pub fn load_data(path: impl AsRef<Path>) -> io::Result<Image> {
+        Self::from_ppm_data(&fs::read(path)?)
+    }
+
+    pub fn from_ppm(bytes: &[u8]) -> io::Result<Image> {
+        Self::parse_ppm(bytes, gamma_to_linear)
+    }
+
+    pub fn from_ppm_data(bytes: &[u8]) -> io::Result<Image> {
+        Self::parse_ppm(bytes, |value| value)
+    }
+
+    fn parse_ppm(bytes: &[u8], decode: impl Fn(f32) -> f32) -> io::Result<Image> {
+        let mut reader = PpmReader { bytes, pos: 0 };
+
+        let magic = reader.token()?;
+        let width = reader.number()?;
+        let height = reader.number()?;
+        let max_value = reader.number()?;
+        if max_value == 0 || max_value > 255 {
+            return Err(invalid("unsupported PPM maximum value"));
+        }
+
+        let count = pixel_count(width, height)? * 3;
+        let values: Vec<usize> = match magic.as_str() {
+            "P3" => (0..count)
+                .map(|_| reader.number())
+                .collect::<io::Result<_>>()?,
+            "P6" => {
+                // A single whitespace byte separates the header from the pixel data.
+                let start = reader.pos + 1;
+                let data = bytes
+                    .get(start..start + count)
+                    .ok_or_else(|| invalid("truncated PPM pixel data"))?;
+                data.iter().map(|&b| b as usize).collect()
+            }
+            _ => return Err(invalid("not a P3 or P6 PPM file")),
+        };
+
+        #[allow(clippy::cast_precision_loss)]
+        let scale = 1.0 / max_value as f32;
+        #[allow(clippy::cast_precision_loss)]
+        let pixels = values
+            .chunks_exact(3)
+            .map(|c| {
+                Vec3::new(
+                    decode(c[0] as f32 * scale),
+                    decode(c[1] as f32 * scale),
+                    decode(c[2] as f32 * scale),
+                )
+            })
+            .collect();
+
+        Ok(Image::new(width, height, pixels))
+    }
+
+    // Radiance RGBE image, either flat or with run-length encoded scanlines.
+    pub fn from_hdr(bytes: &[u8]) -> io::Result<Image> {
+        let mut reader = HdrReader { bytes, pos: 0 };
+
+        if !reader.line()?.starts_with("#?") {
+            return Err(invalid("not a Radiance HDR file"));
+        }
+        loop {
+            let line = reader.line()?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid("unsupported HDR pixel format"));
+            }
+        }
+
+        let resolution = reader.line()?;
+        let (height, width): (usize, usize) =
+            match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+                ["-Y", height, "+X", width] => (
+                    height
+                        .parse()
+                        .map_err(|_| invalid("malformed HDR height"))?,
+                    width.parse().map_err(|_| invalid("malformed HDR width"))?,
+                ),
+                _ => return Err(invalid("unsupported HDR orientation")),
+            };
+
+        let mut rgbe = vec![[0_u8; 4]; pixel_count(width, height)?];
+        for row in rgbe.chunks_exact_mut(width) {
+            reader.scanline(row)?;
+        }
+
+        let pixels = rgbe
+            .iter()
+            .map(|&[r, g, b, e]| {
+                if e == 0 {
+                    return Vec3::default();
+                }
+                let scale = f32::powi(2.0, i32::from(e) - 136);
+                Vec3::new(
+                    (f32::from(r) + 0.5) * scale,
+                    (f32::from(g) + 0.5) * scale,
+                    (f32::from(b) + 0.5) * scale,
+                )
+            })
+            .collect();
+
+        Ok(Image::new(width, height, pixels))
+    }
+
+    // Scanline OpenEXR image with half, float or integer RGB channels, or a single luminance
+    // channel. Only uncompressed files load: ZIP, PIZ and the other compression schemes, like
+    // tiled files, fail as unsupported, so save with compression set to none.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn from_exr(bytes: &[u8]) -> io::Result<Image> {
+        let mut reader = ExrReader { bytes, pos: 0 };
+
+        if reader.u32()? != 20_000_630 {
+            return Err(invalid("not an OpenEXR file"));
+        }
+        if reader.u32()? & 0x1e00 != 0 {
+            return Err(unsupported("tiled, deep and multi-part OpenEXR files"));
+        }
+
+        let mut channels = Vec::new();
+        let mut compression = 0;
+        let mut data_window = None;
+        loop {
+            let name = reader.string()?;
+            if name.is_empty() {
+                break;
+            }
+            let _kind = reader.string()?;
+            let size = reader.u32()? as usize;
+            let end = reader.pos + size;
+
+            match name.as_str() {
+                "channels" => loop {
+                    let channel = reader.string()?;
+                    if channel.is_empty() {
+                        break;
+                    }
+                    let pixel_type = reader.u32()?;
+                    reader.skip(12)?; // pLinear, reserved bytes and subsampling
+                    channels.push((channel, pixel_type));
+                },
+                "compression" => compression = reader.u8()?,
+                "dataWindow" => {
+                    let mut window = [0; 4];
+                    for value in &mut window {
+                        *value = reader.u32()? as i32;
+                    }
+                    data_window = Some(window);
+                }
+                _ => {}
+            }
+            reader.pos = end;
+        }
+
+        if compression != 0 {
+            return Err(unsupported("compressed OpenEXR files"));
+        }
+        let [x_min, y_min, x_max, y_max] =
+            data_window.ok_or_else(|| invalid("OpenEXR file without a data window"))?;
+        // Work in i64 so windows spanning most of the i32 range don't overflow.
+        let extent = |min: i32, max: i32| {
+            usize::try_from(i64::from(max) - i64::from(min) + 1)
+                .map_err(|_| invalid("empty or inverted OpenEXR data window"))
+        };
+        let width = extent(x_min, x_max)?;
+        let height = extent(y_min, y_max)?;
+        let count = pixel_count(width, height)?;
+
+        // Channels are stored in the order of the channel list, one scanline per block.
+        let mut planes = vec![vec![0.0; count]; channels.len()];
+        for _ in 0..height {
+            let offset = reader.u64()? as usize;
+            let mut block = ExrReader { bytes, pos: offset };
+            let y = i64::from(block.u32()? as i32) - i64::from(y_min);
+            block.skip(4)?; // Data size
+            if !(0..height as i64).contains(&y) {
+                return Err(invalid("OpenEXR scanline outside the data window"));
+            }
+            let row = y as usize * width;
+            for (plane, (_, pixel_type)) in planes.iter_mut().zip(&channels) {
+                for value in &mut plane[row..row + width] {
+                    *value = match pixel_type {
+                        0 => block.u32()? as f32,
+                        1 => half_to_f32(block.u16()?),
+                        2 => f32::from_bits(block.u32()?),
+                        _ => return Err(invalid("unknown OpenEXR pixel type")),
+                    };
+                }
+            }
+        }
+
+        let plane = |name: &str| {
+            channels
+                .iter()
+                .position(|(channel, _)| channel == name)
+                .map(|i| &planes[i])
+        };
+        let pixels = match (plane("R"), plane("G"), plane("B"), plane("Y")) {
+            (Some(r), Some(g), Some(b), _) => {
+                (0..count).map(|i| Vec3::new(r[i], g[i], b[i])).collect()
+            }
+            (_, _, _, Some(y)) => y.iter().map(|&v| Vec3::new(v, v, v)).collect(),
+            _ => return Err(invalid("OpenEXR file without RGB or Y channels")),
+        };
+
+        Ok(Image::new(width, height, pixels))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Mean of all the pixels, black for an empty image.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average(&self) -> Vec3 {
+        if self.pixels.is_empty() {
+            return Vec3::default();
+        }
+        let sum = self.pixels.iter().fold(Vec3::default(), |sum, &p| sum + p);
+        sum / self.pixels.len() as f32
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+
+    // Nearest pixel lookup for texture coordinates in [0, 1], with v pointing up.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample(&self, u: f32, v: f32) -> Vec3 {
+        if self.width == 0 || self.height == 0 {
+            return Vec3::new(0.0, 1.0, 1.0);
+        }
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+
+        let x = (u * self.width as f32) as usize;
+        let y = (v * self.height as f32) as usize;
+        self.pixel(x, y)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// Number of pixels in an image of the given size, rejecting empty and oversized images.
+fn pixel_count(width: usize, height: usize) -> io::Result<usize> {
+    match width.checked_mul(height) {
+        Some(0) => Err(invalid("empty image")),
+        Some(count) if count <= MAX_PIXELS => Ok(count),
+        _ => Err(invalid("image too large")),
+    }
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(ErrorKind::Unsupported, format!("{what} are not supported"))
+}
+
+// Converts an IEEE 754 half precision float to single precision.
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((h >> 10) & 0x1f);
+    let mantissa = f32::from(h & 0x3ff);
+
+    match exponent {
+        0 => sign * mantissa * f32::powi(2.0, -24),
+        31 if mantissa == 0.0 => sign * f32::INFINITY,
+        31 => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * f32::powi(2.0, exponent - 15),
+    }
+}
+
+// Reads the header lines and scanlines of a Radiance HDR file.
+struct HdrReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl HdrReader<'_> {
+    fn line(&mut self) -> io::Result<String> {
+        let rest = &self.bytes[self.pos.min(self.bytes.len())..];
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("truncated HDR header"))?;
+        self.pos += end + 1;
+        Ok(String::from_utf8_lossy(&rest[..end]).trim().to_string())
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| invalid("truncated HDR pixel data"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn scanline(&mut self, row: &mut [[u8; 4]]) -> io::Result<()> {
+        let width = row.len();
+        let header = self.bytes.get(self.pos..self.pos + 4);
+        #[allow(clippy::cast_possible_truncation)]
+        let rle = (8..0x8000).contains(&width)
+            && header == Some(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+
+        if !rle {
+            // Flat scanline of RGBE quadruples.
+            for pixel in row {
+                for c in pixel {
+                    *c = self.byte()?;
+                }
+            }
+            return Ok(());
+        }
+
+        // Each channel is run-length encoded separately.
+        self.pos += 4;
+        for c in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = self.byte()?;
+                let (n, run) = if count > 128 {
+                    (usize::from(count - 128), true)
+                } else {
+                    (usize::from(count), false)
+                };
+                if n == 0 || x + n > width {
+                    return Err(invalid("bad HDR run length"));
+                }
+                let value = if run { self.byte()? } else { 0 };
+                for pixel in &mut row[x..x + n] {
+                    pixel[c] = if run { value } else { self.byte()? };
+                }
+                x += n;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Little endian reader for OpenEXR headers and scanline blocks.
+struct ExrReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl ExrReader<'_> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| invalid("truncated OpenEXR data"))?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn skip(&mut self, n: usize) -> io::Result<()> {
+        if self.pos + n > self.bytes.len() {
+            return Err(invalid("truncated OpenEXR data"));
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let rest = &self.bytes[self.pos.min(self.bytes.len())..];
+        let end = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("truncated OpenEXR header"))?;
+        self.pos += end + 1;
+        Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+    }
+}
+
+// Splits a PPM header into whitespace separated tokens, skipping comments.
+struct PpmReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl PpmReader<'_> {
+    fn token(&mut self) -> io::Result<String> {
+        let bytes = self.bytes;
+        loop {
+            while self.pos < bytes.len() && bytes[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            if self.pos < bytes.len() && bytes[self.pos] == b'#' {
+                while self.pos < bytes.len() && bytes[self.pos] != b'\n' {
+                    self.pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+
+        let start = self.pos;
+        while self.pos < bytes.len() && !bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(invalid("unexpected end of PPM data"));
+        }
+        Ok(String::from_utf8_lossy(&bytes[start..self.pos]).into_owned())
+    }
+
+    fn number(&mut self) -> io::Result<usize> {
+        self.token()?
+            .parse()
+            .map_err(|_| invalid("malformed number in PPM data"))
+    }
+}
+
+#[test]
+fn test_from_ppm() {
+    let image = Image::from_ppm(b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+    assert_eq!(image.width(), 2);
+    assert_eq!(image.pixel(0, 0), Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(image.sample(0.9, 0.5), Vec3::new(0.0, 0.0, 1.0));
+
+    let binary = Image::from_ppm(b"P6 1 1 255\n\xff\x00\xff").unwrap();
+    assert_eq!(binary.pixel(0, 0), Vec3::new(1.0, 0.0, 1.0));
+
+    // Empty images and sizes that overflow are rejected rather than loaded.
+    for header in ["P3 0 1 255\n", "P6 4294967296 4294967296 255\n"] {
+        let error = Image::from_ppm(header.as_bytes()).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn test_from_hdr() {
+    // Flat scanline: 2^(129 - 136) * 128.5 is just over one.
+    let mut flat = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+    flat.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+    let image = Image::from_hdr(&flat).unwrap();
+    assert!((image.pixel(0, 0) - Vec3::new(1.0, 0.5, 0.0)).length() < 0.01);
+    assert_eq!(image.pixel(1, 0), Vec3::default());
+
+    // Run-length encoded scanline of eight pixels.
+    let mut rle = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+    rle.extend_from_slice(&[2, 2, 0, 8, 136, 128, 136, 128, 136, 128, 136, 130]);
+    let image = Image::from_hdr(&rle).unwrap();
+    assert!((image.pixel(7, 0) - Vec3::new(2.0, 2.0, 2.0)).length() < 0.02);
+
+    let empty = Image::from_hdr(b"#?RADIANCE\n\n-Y 0 +X 8\n").err().unwrap();
+    assert_eq!(empty.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_from_exr() {
+    // A 2x1 uncompressed image with half channels.
+    let mut exr = Vec::new();
+    exr.extend_from_slice(&20_000_630_u32.to_le_bytes());
+    exr.extend_from_slice(&2_u32.to_le_bytes());
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        exr.extend_from_slice(name.as_bytes());
+        exr.push(0);
+        exr.extend_from_slice(kind.as_bytes());
+        exr.push(0);
+        exr.extend_from_slice(&u32::try_from(value.len()).unwrap().to_le_bytes());
+        exr.extend_from_slice(value);
+    };
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+    }
+    channels.push(0);
+    attribute("channels", "chlist", &channels);
+    attribute("compression", "compression", &[0]);
+    attribute(
+        "dataWindow",
+        "box2i",
+        &[0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    );
+    exr.push(0);
+
+    let offset = exr.len() as u64 + 8;
+    exr.extend_from_slice(&offset.to_le_bytes());
+    exr.extend_from_slice(&[0, 0, 0, 0, 12, 0, 0, 0]);
+    // Halves for 0.5 and 2.0, then 1.0 and 0.0, then 4.0 and 1.0.
+    exr.extend_from_slice(&[
+        0x00, 0x38, 0x00, 0x40, 0x00, 0x3c, 0x00, 0x00, 0x00, 0x44, 0x00, 0x3c,
+    ]);
+
+    let image = Image::from_exr(&exr).unwrap();
+    assert_eq!(image.width(), 2);
+    assert_eq!(image.pixel(0, 0), Vec3::new(4.0, 1.0, 0.5));
+    assert_eq!(image.pixel(1, 0), Vec3::new(1.0, 0.0, 2.0));
+
+    // Compression isn't supported.
+    let tag = b"compression\0compression\0";
+    let at = exr.windows(tag.len()).position(|w| w == tag).unwrap() + tag.len() + 4;
+    exr[at] = 1;
+    let error = Image::from_exr(&exr).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::Unsupported);
+    exr[at] = 0;
+
+    // Inverted and oversized data windows are invalid, even where their size overflows i32.
+    let tag = b"dataWindow\0box2i\0";
+    let at = exr.windows(tag.len()).position(|w| w == tag).unwrap() + tag.len() + 4;
+    for window in [
+        [0, 0, -1, 0],
+        [i32::MIN, 0, i32::MAX, 0],
+        [0, 0, 1 << 20, 1 << 20],
+    ] {
+        for (bytes, value) in exr[at..at + 16].chunks_exact_mut(4).zip(window) {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+        let error = Image::from_exr(&exr).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -1,17 +1,48 @@
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Interval {
     pub min: f32,
     pub max: f32,
 }
 
 impl Interval {
+    pub const EMPTY: Interval = Interval {
+        min: f32::INFINITY,
+        max: f32::NEG_INFINITY,
+    };
+
+    pub const UNIVERSE: Interval = Interval {
+        min: f32::NEG_INFINITY,
+        max: f32::INFINITY,
+    };
+
     pub fn new(min: f32, max: f32) -> Self {
         Interval { min, max }
     }
 
+    // Create the interval tightly enclosing the two input intervals.
+    pub fn from_intervals(a: &Interval, b: &Interval) -> Self {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    pub fn size(&self) -> f32 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, x: f32) -> bool {
+        self.min <= x && x <= self.max
+    }
+
     pub fn surrounds(&self, x: f32) -> bool {
         self.min < x && x < self.max
     }
+
+    pub fn expand(&self, delta: f32) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
 }
 
 impl Default for Interval {
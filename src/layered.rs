@@ -0,0 +1,305 @@
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Lobe},
+    fresnel::fr_dielectric,
+    hittable::HitRecord,
+    material::Material,
+    microfacet::{cos_theta, reflect_local, TrowbridgeReitz},
+    ray::Ray,
+    texture::Texture,
+    util::rand_f32,
+    vec3::{dot, Vec3},
+};
+
+// A dielectric coating over an arbitrary base material, such as car paint or varnished wood.
+//
+// The layers are combined analytically: light reflects off the coating according to Fresnel, and
+// whatever gets through reaches the base, attenuated by absorption in the coating along the
+// refracted path in and out.
+#[derive(Clone)]
+pub struct Layered {
+    pub base: Material,
+    pub coat_ior: f32,
+    pub coat_distrib: TrowbridgeReitz,
+    pub coat_absorption: Vec3, // Absorption coefficient per unit thickness of the coating
+    pub thickness: f32,
+}
+
+impl Layered {
+    pub fn new(base: Material, coat_ior: f32, coat_roughness: f32) -> Self {
+        Layered {
+            base,
+            coat_ior,
+            coat_distrib: TrowbridgeReitz::from_roughness(coat_roughness, 0.0),
+            coat_absorption: Vec3::default(),
+            thickness: 0.0,
+        }
+    }
+
+    // Tint the coating with an absorption coefficient and a thickness.
+    pub fn with_absorption(mut self, absorption: Vec3, thickness: f32) -> Self {
+        self.coat_absorption = absorption;
+        self.thickness = thickness;
+        self
+    }
+
+    // Fresnel reflectance of the coating seen from outside.
+    fn fresnel(&self, cos_theta: f32) -> f32 {
+        fr_dielectric(cos_theta.abs(), self.coat_ior)
+    }
+
+    // Fraction of light that makes it through the coating to the base and back out again.
+    fn base_attenuation(&self, r_in: &Ray, cos_theta_o: f32, cos_theta_i: f32) -> Vec3 {
+        let refracted_cos = |cos: f32| {
+            let sin2 = (1.0 - cos * cos).max(0.0) / (self.coat_ior * self.coat_ior);
+            f32::sqrt(1.0 - sin2).max(1e-4)
+        };
+        let path =
+            self.thickness * (1.0 / refracted_cos(cos_theta_o) + 1.0 / refracted_cos(cos_theta_i));
+        let transmit = |sigma_a: f32| f32::exp(-sigma_a * path);
+        let absorption = Vec3::new(
+            transmit(self.coat_absorption.x()),
+            transmit(self.coat_absorption.y()),
+            transmit(self.coat_absorption.z()),
+        );
+
+        (1.0 - self.fresnel(cos_theta_o))
+            * (1.0 - self.fresnel(cos_theta_i))
+            * r_in.spectrum(&absorption)
+    }
+
+    // Evaluates the glossy coating reflection for local directions.
+    fn coat_eval(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        if self.coat_distrib.effectively_smooth() || cos_theta(wi) <= 0.0 {
+            return 0.0;
+        }
+        let wm = (*wo + *wi).normalize();
+        self.coat_distrib.d(&wm) * self.coat_distrib.g(wo, wi) * self.fresnel(dot(wo, &wm))
+            / (4.0 * cos_theta(wo))
+    }
+
+    fn coat_pdf(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        if self.coat_distrib.effectively_smooth() || cos_theta(wi) <= 0.0 {
+            return 0.0;
+        }
+        let wm = (*wo + *wi).normalize();
+        self.coat_distrib.pdf(wo, &wm) / (4.0 * dot(wo, &wm))
+    }
+
+    // Probability of sampling the coating rather than the base.
+    fn coat_probability(&self, wo: &Vec3) -> f32 {
+        self.fresnel(cos_theta(wo)).clamp(0.05, 0.95)
+    }
+}
+
+impl Bsdf for Layered {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        if cos_theta(&wo) <= 0.0 {
+            return None;
+        }
+        let p_coat = self.coat_probability(&wo);
+
+        if rand_f32() < p_coat {
+            if self.coat_distrib.effectively_smooth() {
+                let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+                let f = self.fresnel(cos_theta(&wo)) / p_coat;
+                return Some(BsdfSample {
+                    direction: uvw.transform(&wi),
+                    weight: Vec3::new(f, f, f),
+                    pdf: p_coat,
+                    lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+                });
+            }
+
+            let wm = self.coat_distrib.sample_wm(&wo, (rand_f32(), rand_f32()));
+            let wi = reflect_local(&wo, &wm);
+            if cos_theta(&wi) <= 0.0 {
+                return None;
+            }
+            let direction = uvw.transform(&wi);
+            let pdf = self.pdf(r_in, rec, &direction);
+            if pdf <= 0.0 {
+                return None;
+            }
+            return Some(BsdfSample {
+                direction,
+                weight: self.eval(r_in, rec, &direction) / pdf,
+                pdf,
+                lobe: Lobe::GLOSSY | Lobe::REFLECTION,
+            });
+        }
+
+        let srec = self.base.sample(r_in, rec)?;
+        let wi = uvw.to_local(&srec.direction.normalize());
+        if srec.lobe.is_specular() {
+            let attenuation = self.base_attenuation(r_in, cos_theta(&wo), cos_theta(&wi));
+            return Some(BsdfSample {
+                weight: srec.weight * attenuation / (1.0 - p_coat),
+                pdf: srec.pdf * (1.0 - p_coat),
+                ..srec
+            });
+        }
+
+        let pdf = self.pdf(r_in, rec, &srec.direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            weight: self.eval(r_in, rec, &srec.direction) / pdf,
+            pdf,
+            ..srec
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        if cos_theta(&wo) <= 0.0 {
+            return Vec3::default();
+        }
+
+        let coat = self.coat_eval(&wo, &wi);
+        let base = self.base.eval(r_in, rec, direction)
+            * self.base_attenuation(r_in, cos_theta(&wo), cos_theta(&wi));
+        Vec3::new(coat, coat, coat) + base
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        if cos_theta(&wo) <= 0.0 {
+            return 0.0;
+        }
+
+        let p_coat = self.coat_probability(&wo);
+        p_coat * self.coat_pdf(&wo, &wi) + (1.0 - p_coat) * self.base.pdf(r_in, rec, direction)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        let cos_theta = dot(&-r_in.direction().normalize(), &rec.normal);
+        self.base.emitted(r_in, rec) * (1.0 - self.fresnel(cos_theta))
+    }
+}
+
+// Blends two materials by a scalar or texture, where an amount of zero gives only `first`.
+#[derive(Clone)]
+pub struct Mix {
+    pub first: Material,
+    pub second: Material,
+    pub amount: Texture,
+}
+
+impl Mix {
+    pub fn new(first: Material, second: Material, amount: impl Into<Texture>) -> Self {
+        Mix {
+            first,
+            second,
+            amount: amount.into(),
+        }
+    }
+
+    fn amount(&self, rec: &HitRecord) -> f32 {
+        self.amount.value(rec.u, rec.v, &rec.p).x().clamp(0.0, 1.0)
+    }
+
+    // Emission averaged over the surface, for picking lights by their power.
+    pub(crate) fn emission(&self) -> Vec3 {
+        let amount = self.amount.average().x().clamp(0.0, 1.0);
+        (1.0 - amount) * self.first.emission() + amount * self.second.emission()
+    }
+}
+
+impl Bsdf for Mix {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let amount = self.amount(rec);
+        let (chosen, p_chosen) = if rand_f32() < amount {
+            (&self.second, amount)
+        } else {
+            (&self.first, 1.0 - amount)
+        };
+
+        let srec = chosen.sample(r_in, rec)?;
+        if srec.lobe.is_specular() {
+            // The other material can't contribute to a specular direction, and the selection
+            // probability cancels with the blend weight.
+            return Some(BsdfSample {
+                pdf: srec.pdf * p_chosen,
+                ..srec
+            });
+        }
+
+        let pdf = self.pdf(r_in, rec, &srec.direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            weight: self.eval(r_in, rec, &srec.direction) / pdf,
+            pdf,
+            ..srec
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        let amount = self.amount(rec);
+        (1.0 - amount) * self.first.eval(r_in, rec, direction)
+            + amount * self.second.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        let amount = self.amount(rec);
+        (1.0 - amount) * self.first.pdf(r_in, rec, direction)
+            + amount * self.second.pdf(r_in, rec, direction)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        let amount = self.amount(rec);
+        (1.0 - amount) * self.first.emitted(r_in, rec) + amount * self.second.emitted(r_in, rec)
+    }
+}
+
+#[test]
+fn test_layered_sample_matches_eval() {
+    let base = Material::Lambartian {
+        albedo: Vec3::new(0.7, 0.1, 0.1),
+    };
+    let mat = Layered::new(base, 1.5, 0.3).with_absorption(Vec3::new(0.5, 0.2, 0.1), 0.5);
+    let rec = HitRecord {
+        normal: Vec3::new(0.0, 1.0, 0.0),
+        front_face: true,
+        ..Default::default()
+    };
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.2, -1.0, -0.7));
+
+    for _ in 0..500 {
+        let Some(srec) = mat.sample(&r_in, &rec) else {
+            continue;
+        };
+        let pdf = mat.pdf(&r_in, &rec, &srec.direction);
+        let expected = mat.eval(&r_in, &rec, &srec.direction) / pdf;
+
+        assert!((pdf - srec.pdf).abs() <= 1e-2 * pdf);
+        assert!((expected - srec.weight).length() <= 1e-2 * expected.length());
+    }
+}
+
+#[test]
+fn test_mix_emission() {
+    let light = Material::DiffuseLight {
+        emit: Vec3::new(4.0, 4.0, 4.0),
+    };
+    let dark = Material::Lambartian {
+        albedo: Vec3::new(0.5, 0.5, 0.5),
+    };
+
+    // Mostly the light, or half and half on average over a checker.
+    let mostly_light = Mix::new(dark.clone(), light.clone(), 0.75);
+    assert_eq!(mostly_light.emission(), Vec3::new(3.0, 3.0, 3.0));
+    let checker = Texture::checker(1.0, 0.0.into(), 1.0.into());
+    assert_eq!(
+        Mix::new(dark, light, checker).emission(),
+        Vec3::new(2.0, 2.0, 2.0)
+    );
+}
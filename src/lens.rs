@@ -0,0 +1,418 @@
+#![allow(clippy::cast_precision_loss)]
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{
+    microfacet::refract_local,
+    util::rand_f32,
+    vec3::{dot, Vec3},
+};
+
+// Bins across the film radius with their own exit pupil bounds, and samples per side of the grid
+// that finds each.
+const PUPIL_BINS: usize = 64;
+const PUPIL_SAMPLES: usize = 64;
+
+// One interface of a lens prescription, front to back, in millimetres. The thickness runs along the
+// axis to the next interface, and the index of refraction is of the glass behind it, towards the
+// film. A curvature radius of 0 marks the aperture stop, with air behind it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LensElement {
+    pub curvature_radius: f32,
+    pub thickness: f32,
+    pub eta: f32,
+    pub aperture_diameter: f32,
+}
+
+// A real camera lens of spherical elements that rays are traced through, after "Realistic camera
+// model" (Kolb, Mitchell and Hanrahan 1995) as done in pbrt. Distortion, vignetting and the change
+// in view as the lens focuses all come out of the tracing.
+//
+// Lens space has the film at the origin, across x and y, and the lens towards -z. The camera puts
+// the film at `look_from` and scales millimetres to world units by `scale`, which defaults to
+// scenes in metres.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+    film_diagonal: f32,                 // Millimetres, 35 mm full frame by default
+    scale: f32,                         // World units per millimetre
+    film_distance: f32,                 // Rear element to film, set by focusing
+    exit_pupils: Vec<Option<[f32; 4]>>, // Bounds on the rear element of rays getting through
+}
+
+impl LensSystem {
+    pub fn new(elements: Vec<LensElement>) -> Self {
+        assert!(!elements.is_empty());
+        LensSystem {
+            film_distance: elements[elements.len() - 1].thickness,
+            elements,
+            film_diagonal: 43.27,
+            scale: 0.001,
+            exit_pupils: Vec::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // Reads a prescription with a row per interface of curvature radius, thickness, index of
+    // refraction and aperture diameter, the format pbrt uses. Lines starting with '#' are comments.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut elements = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(|s| {
+                    s.parse::<f32>()
+                        .map_err(|_| invalid("malformed lens number"))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            let [curvature_radius, thickness, eta, aperture_diameter] = values[..] else {
+                return Err(invalid("lens rows need four columns"));
+            };
+            elements.push(LensElement {
+                curvature_radius,
+                thickness,
+                eta,
+                aperture_diameter,
+            });
+        }
+        if elements.is_empty() {
+            return Err(invalid("lens prescription without elements"));
+        }
+        Ok(Self::new(elements))
+    }
+
+    pub fn with_film_diagonal(mut self, millimetres: f32) -> Self {
+        self.film_diagonal = millimetres;
+        self
+    }
+
+    pub fn with_scale(mut self, world_units_per_millimetre: f32) -> Self {
+        self.scale = world_units_per_millimetre;
+        self
+    }
+
+    // Stops the aperture down to the given diameter, up to the widest the stop opens.
+    pub fn with_stop_diameter(mut self, millimetres: f32) -> Self {
+        for element in &mut self.elements {
+            if element.curvature_radius == 0.0 {
+                element.aperture_diameter = millimetres.min(element.aperture_diameter);
+            }
+        }
+        self
+    }
+
+    pub fn film_diagonal(&self) -> f32 {
+        self.film_diagonal
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    // Effective focal length in millimetres, from a paraxial ray traced through the lens.
+    pub fn focal_length(&self) -> Option<f32> {
+        self.thick_lens()
+            .map(|(film_principal, film_focal, _)| film_focal - film_principal)
+    }
+
+    // Diameter in millimetres the aperture stop opens to, if the lens has one.
+    pub fn stop_diameter(&self) -> Option<f32> {
+        self.elements
+            .iter()
+            .find(|element| element.curvature_radius == 0.0)
+            .map(|element| element.aperture_diameter)
+    }
+
+    // Focal length over the stop diameter.
+    pub fn f_number(&self) -> Option<f32> {
+        let stop_diameter = self.stop_diameter().filter(|&d| d > 0.0)?;
+        Some(self.focal_length()? / stop_diameter)
+    }
+
+    // Thickness behind an element, with the film distance in place of the last.
+    fn thickness(&self, index: usize) -> f32 {
+        if index + 1 == self.elements.len() {
+            self.film_distance
+        } else {
+            self.elements[index].thickness
+        }
+    }
+
+    fn rear_z(&self) -> f32 {
+        -self.film_distance
+    }
+
+    fn front_z(&self) -> f32 {
+        -(0..self.elements.len())
+            .map(|i| self.thickness(i))
+            .sum::<f32>()
+    }
+
+    // Hits the interface whose vertex is at `z`, returning the point and the normal facing the
+    // ray, or None if the ray misses or is blocked by the element's rim.
+    fn intersect(
+        element: &LensElement,
+        z: f32,
+        origin: Vec3,
+        direction: Vec3,
+    ) -> Option<(Vec3, Vec3)> {
+        let radius = element.curvature_radius;
+        let (t, normal) = if radius == 0.0 {
+            let t = (z - origin.z()) / direction.z();
+            (t, Vec3::new(0.0, 0.0, -direction.z().signum()))
+        } else {
+            let o = origin - Vec3::new(0.0, 0.0, z + radius);
+            let a = direction.length_squared();
+            let b = 2.0 * dot(&direction, &o);
+            let c = o.length_squared() - radius * radius;
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let root = discriminant.sqrt();
+            let (t0, t1) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+            // The interface is the half of the sphere facing its vertex.
+            let t = if (direction.z() > 0.0) ^ (radius < 0.0) {
+                t0.min(t1)
+            } else {
+                t0.max(t1)
+            };
+            let normal = (o + t * direction).normalize();
+            let normal = if dot(&normal, &direction) > 0.0 {
+                -normal
+            } else {
+                normal
+            };
+            (t, normal)
+        };
+        if t.is_nan() || t <= 0.0 {
+            return None;
+        }
+
+        let p = origin + t * direction;
+        let aperture_radius = 0.5 * element.aperture_diameter;
+        if p.x() * p.x() + p.y() * p.y() > aperture_radius * aperture_radius {
+            return None;
+        }
+        Some((p, normal))
+    }
+
+    // Index of refraction on each side of element `i`: the object side, then the film side.
+    fn etas(&self, i: usize) -> (f32, f32) {
+        let eta = |e: f32| if e == 0.0 { 1.0 } else { e };
+        let front = if i == 0 {
+            1.0
+        } else {
+            eta(self.elements[i - 1].eta)
+        };
+        (front, eta(self.elements[i].eta))
+    }
+
+    // Follows a ray in lens space from the film out through the front element, or None if the
+    // lens blocks it.
+    fn trace_from_film(&self, origin: Vec3, direction: Vec3) -> Option<(Vec3, Vec3)> {
+        let (mut origin, mut direction) = (origin, direction.normalize());
+        let mut z = 0.0;
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z -= self.thickness(i);
+            let (p, normal) = Self::intersect(element, z, origin, direction)?;
+            origin = p;
+            if element.curvature_radius != 0.0 {
+                let (front, back) = self.etas(i);
+                direction = refract_local(&-direction, &normal, front / back)?.normalize();
+            }
+        }
+        Some((origin, direction))
+    }
+
+    // Follows a ray in lens space from the scene in through the rear element.
+    fn trace_from_scene(&self, origin: Vec3, direction: Vec3) -> Option<(Vec3, Vec3)> {
+        let (mut origin, mut direction) = (origin, direction.normalize());
+        let mut z = self.front_z();
+        for (i, element) in self.elements.iter().enumerate() {
+            let (p, normal) = Self::intersect(element, z, origin, direction)?;
+            origin = p;
+            if element.curvature_radius != 0.0 {
+                let (front, back) = self.etas(i);
+                direction = refract_local(&-direction, &normal, back / front)?.normalize();
+            }
+            z += self.thickness(i);
+        }
+        Some((origin, direction))
+    }
+
+    // Principal plane and focal point along the axis of a ray parallel to it going in, from where
+    // it comes out.
+    fn cardinal_points(origin_in: Vec3, (origin, direction): (Vec3, Vec3)) -> (f32, f32) {
+        let focal = origin + (-origin.x() / direction.x()) * direction;
+        let principal = origin + ((origin_in.x() - origin.x()) / direction.x()) * direction;
+        (principal.z(), focal.z())
+    }
+
+    // Treats the lens as a thick lens, from paraxial rays traced through both ways. Gives the
+    // film side principal plane and focal point, and the scene side principal plane.
+    fn thick_lens(&self) -> Option<(f32, f32, f32)> {
+        let height = 0.001 * self.film_diagonal;
+        let from_scene = Vec3::new(height, 0.0, self.front_z() - 1.0);
+        let from_film = Vec3::new(height, 0.0, self.rear_z() + 1.0);
+        let to_film = self.trace_from_scene(from_scene, Vec3::new(0.0, 0.0, 1.0))?;
+        let to_scene = self.trace_from_film(from_film, Vec3::new(0.0, 0.0, -1.0))?;
+        let (film_principal, film_focal) = Self::cardinal_points(from_scene, to_film);
+        let (scene_principal, _) = Self::cardinal_points(from_film, to_scene);
+        Some((film_principal, film_focal, scene_principal))
+    }
+
+    // Copy of the lens focused at a distance in millimetres from the film, or at infinity for
+    // anything not positive and finite, with its exit pupils found.
+    pub fn focused(&self, distance: f32) -> LensSystem {
+        let mut lens = self.clone();
+
+        if let Some((film_principal, film_focal, scene_principal)) = self.thick_lens() {
+            let focal_length = film_focal - film_principal;
+
+            // Moving the lens out by `delta` must put the image on the film, for an object that
+            // stays put. The object and image distances add up to their sum times the focal length.
+            let image_distance = if distance > 0.0 && distance.is_finite() {
+                let sum = scene_principal + distance - film_principal;
+                let discriminant = (sum * sum - 4.0 * focal_length * sum).max(0.0);
+                0.5 * (sum - discriminant.sqrt())
+            } else {
+                focal_length
+            };
+            let delta = image_distance + film_principal;
+            lens.film_distance = self.film_distance + delta;
+        }
+
+        lens.exit_pupils = (0..PUPIL_BINS)
+            .map(|bin| lens.find_exit_pupil(bin))
+            .collect();
+        lens
+    }
+
+    // Bounds on the rear element of the rays from a bin of film radii that make it out of the
+    // lens, for points along +x. None when the lens blocks everything.
+    fn find_exit_pupil(&self, bin: usize) -> Option<[f32; 4]> {
+        let film_radius = 0.5 * self.film_diagonal;
+        let (r0, r1) = (
+            film_radius * bin as f32 / PUPIL_BINS as f32,
+            film_radius * (bin + 1) as f32 / PUPIL_BINS as f32,
+        );
+        let rear = &self.elements[self.elements.len() - 1];
+        let extent = 1.5 * 0.5 * rear.aperture_diameter;
+        let step = 2.0 * extent / PUPIL_SAMPLES as f32;
+
+        let mut bounds: Option<[f32; 4]> = None;
+        for k in 0..PUPIL_SAMPLES * PUPIL_SAMPLES {
+            let (sx, sy) = (k % PUPIL_SAMPLES, k / PUPIL_SAMPLES);
+            let film = Vec3::new(
+                r0 + (r1 - r0) * (k as f32 + 0.5) / (PUPIL_SAMPLES * PUPIL_SAMPLES) as f32,
+                0.0,
+                0.0,
+            );
+            let x = -extent + (sx as f32 + 0.5) * step;
+            let y = -extent + (sy as f32 + 0.5) * step;
+            let rear_point = Vec3::new(x, y, self.rear_z());
+            if self.trace_from_film(film, rear_point - film).is_some() {
+                bounds = Some(match bounds {
+                    None => [x, y, x, y],
+                    Some([x0, y0, x1, y1]) => [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+                });
+            }
+        }
+
+        // Pad the bounds by the sample spacing, which may have stepped over their edges.
+        bounds.map(|[x0, y0, x1, y1]| [x0 - step, y0 - step, x1 + step, y1 + step])
+    }
+
+    // Ray in lens space leaving the front of a focused lens from a point on the film, and how much
+    // light it carries relative to the center of the film. None where the lens blocks it.
+    pub fn sample_ray(&self, film: Vec3) -> Option<(Vec3, Vec3, f32)> {
+        let film_radius = (film.x() * film.x() + film.y() * film.y()).sqrt();
+        let bin = ((film_radius / (0.5 * self.film_diagonal) * PUPIL_BINS as f32) as usize)
+            .min(PUPIL_BINS - 1);
+        let [x0, y0, x1, y1] = (*self.exit_pupils.get(bin)?)?;
+
+        // Pupil bounds are for film points along +x, so turn the point on them to match.
+        let x = x0 + rand_f32() * (x1 - x0);
+        let y = y0 + rand_f32() * (y1 - y0);
+        let (cos, sin) = if film_radius > 0.0 {
+            (film.x() / film_radius, film.y() / film_radius)
+        } else {
+            (1.0, 0.0)
+        };
+        let rear_point = Vec3::new(cos * x - sin * y, sin * x + cos * y, self.rear_z());
+
+        let direction = (rear_point - film).normalize();
+        let (origin, out) = self.trace_from_film(film, direction)?;
+
+        // Light falls off with the fourth power of the cosine off axis, and with the pupil area.
+        let area = |[x0, y0, x1, y1]: [f32; 4]| (x1 - x0) * (y1 - y0);
+        let center_area = self.exit_pupils[0].map_or(0.0, area);
+        let cos2 = direction.z() * direction.z();
+        let weight = if center_area > 0.0 {
+            cos2 * cos2 * area([x0, y0, x1, y1]) / center_area
+        } else {
+            cos2 * cos2
+        };
+        Some((origin, out, weight))
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+#[test]
+fn test_lens_system() {
+    // Double Gauss lens scaled to 50 mm, from US patent 2,673,491.
+    let lens = LensSystem::parse(
+        "# radius thickness eta aperture\n\
+         29.475 3.76 1.67 25.2\n84.83 0.12 1 25.2\n19.275 4.025 1.67 23\n\
+         40.77 3.275 1.699 23\n12.75 5.705 1 18\n0 4.5 0 17.1\n\
+         -14.495 1.18 1.603 17\n40.77 6.065 1.658 20\n-20.385 0.19 1 20\n\
+         437.065 3.22 1.717 20\n-39.73 5 1 20\n",
+    )
+    .unwrap();
+    assert!(LensSystem::parse("1 2 3\n").is_err());
+
+    // Focused at infinity, a ray parallel to the axis comes to a point on the film, about 50 mm
+    // behind the principal plane.
+    let infinity = lens.focused(f32::INFINITY);
+    let from_scene = Vec3::new(2.0, 0.0, infinity.front_z() - 1.0);
+    let (origin, direction) = infinity
+        .trace_from_scene(from_scene, Vec3::new(0.0, 0.0, 1.0))
+        .unwrap();
+    let at_film = origin + (-origin.z() / direction.z()) * direction;
+    assert!(at_film.x().abs() < 0.05);
+    let (principal, focal) = LensSystem::cardinal_points(from_scene, (origin, direction));
+    assert!((focal - principal - 50.0).abs() < 1.5);
+    assert!((infinity.focal_length().unwrap() - 50.0).abs() < 1.5);
+    assert!((lens.f_number().unwrap() - 50.0 / 17.1).abs() < 0.1);
+
+    // Focusing closer moves the lens out, and the film center sees through it, except past the
+    // round pupil in the corners of its bounds.
+    let near = lens.focused(1000.0);
+    assert!(near.film_distance > infinity.film_distance);
+    let rays: Vec<_> = (0..100)
+        .filter_map(|_| near.sample_ray(Vec3::default()))
+        .collect();
+    assert!(rays.len() > 50);
+    for (_, direction, weight) in rays {
+        assert!(direction.z() < -0.99 && weight > 0.5);
+    }
+
+    // Stopping down shrinks the exit pupil.
+    let stopped = lens.with_stop_diameter(4.0).focused(f32::INFINITY);
+    let area = |[x0, y0, x1, y1]: [f32; 4]| (x1 - x0) * (y1 - y0);
+    assert!(area(stopped.exit_pupils[0].unwrap()) < area(infinity.exit_pupils[0].unwrap()));
+}
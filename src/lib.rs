@@ -0,0 +1,42 @@
+pub mod aabb;
+pub mod aperture;
+pub mod background;
+pub mod bsdf;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod conductor;
+pub mod dielectric;
+pub mod diffuse;
+pub mod environment;
+pub mod exposure;
+pub mod fresnel;
+pub mod hittable;
+pub mod hittable_list;
+pub mod ies;
+pub mod image;
+pub mod interval;
+pub mod layered;
+pub mod lens;
+pub mod light;
+pub mod light_sampler;
+pub mod mapped;
+pub mod masked;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+pub mod onb;
+pub mod principled;
+pub mod quad;
+pub mod ray;
+pub mod sampling;
+pub mod sky;
+pub mod spectrum;
+pub mod sphere;
+pub mod subsurface;
+pub mod texture;
+pub mod util;
+pub mod vec3;
+
+pub use vec3::Vec3;
@@ -0,0 +1,414 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    bsdf::Bsdf,
+    color::luminance,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableObject,
+    ies::IesProfile,
+    interval::Interval,
+    onb::Onb,
+    ray::Ray,
+    texture::Texture,
+    util::rand_f32,
+    vec3::{dot, random_in_cone, random_vec},
+    Vec3,
+};
+
+// Lights for next event estimation. Punctual lights can't be hit by rays, so they only contribute
+// through light sampling, and positional ones fall off with the inverse square of the distance,
+// faded out to nothing at their range if they have one. Area lights are emissive objects that are also in the world, so rays can find them both ways.
+//
+// Point and spot lights can take an IES profile, whose candelas then scale `intensity`. The nadir
+// of the profile points down for point lights and along the axis for spotlights, with horizontal
+// angles starting from +x, or the direction closest to it.
+#[derive(Clone)]
+pub enum Light {
+    Point {
+        position: Vec3,
+        intensity: Vec3, // Radiant intensity, power per steradian
+        radius: f32,     // Size of the light for soft shadows, zero for hard ones
+        range: f32,      // Distance the light reaches, infinite for plain inverse square falloff
+        profile: Option<Arc<IesProfile>>,
+    },
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        intensity: Vec3, // Radiant intensity along the axis
+        cos_inner: f32,  // Full intensity inside this cone
+        cos_outer: f32,  // No light outside this cone
+        radius: f32,
+        range: f32,
+        profile: Option<Arc<IesProfile>>,
+        gobo: Option<Texture>, // Image projected across the outer cone
+    },
+    Directional {
+        direction: Vec3,  // Direction the light travels in
+        irradiance: Vec3, // Power per unit area facing the light
+        cos_angle: f32,   // Angular radius of the source for soft shadows, one for hard ones
+    },
+    Area(HittableObject),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LightSample {
+    pub direction: Vec3, // Unit direction from the shaded point towards the light
+    pub distance: f32,   // Distance to the light, infinite for directional lights
+    pub radiance: Vec3,  // Light arriving at the shaded point, in RGB
+    pub pdf: f32,        // Solid angle density for area lights, one for punctual lights
+}
+
+impl Light {
+    pub fn point(position: Vec3, intensity: Vec3) -> Self {
+        Light::Point {
+            position,
+            intensity,
+            radius: 0.0,
+            range: f32::INFINITY,
+            profile: None,
+        }
+    }
+
+    // Cone angles are in degrees from the axis, with the light fading out between them.
+    pub fn spot(position: Vec3, look_at: Vec3, intensity: Vec3, inner: f32, outer: f32) -> Self {
+        assert!(
+            look_at != position,
+            "a spot light cannot look at its own position"
+        );
+        let outer = outer.max(inner);
+        Light::Spot {
+            position,
+            direction: (look_at - position).normalize(),
+            intensity,
+            cos_inner: inner.to_radians().cos(),
+            cos_outer: outer.to_radians().cos(),
+            radius: 0.0,
+            range: f32::INFINITY,
+            profile: None,
+            gobo: None,
+        }
+    }
+
+    pub fn directional(direction: Vec3, irradiance: Vec3) -> Self {
+        Light::Directional {
+            direction: direction.normalize(),
+            irradiance,
+            cos_angle: 1.0,
+        }
+    }
+
+    // An emissive sphere, quad or triangle, which should also be added to the world.
+    pub fn area(object: HittableObject) -> Self {
+        Light::Area(object)
+    }
+
+    // Area lights for the emissive objects in a list, such as the triangles of a light panel or
+    // neon sign mesh.
+    pub fn emitters(objects: &[HittableObject]) -> Vec<Light> {
+        objects
+            .iter()
+            .filter(|object| {
+                let emission = object.material().map_or(Vec3::default(), |m| m.emission());
+                emission != Vec3::default() && object.area() > 0.0
+            })
+            .map(|object| Light::area(object.clone()))
+            .collect()
+    }
+
+    // Gives the light a size for soft shadows: a radius in world units for point and spot lights,
+    // and an angular radius in degrees for directional ones.
+    pub fn with_radius(mut self, size: f32) -> Self {
+        match &mut self {
+            Light::Point { radius, .. } | Light::Spot { radius, .. } => *radius = size,
+            Light::Directional { cos_angle, .. } => *cos_angle = size.to_radians().cos(),
+            Light::Area(_) => {}
+        }
+        self
+    }
+
+    // Limits how far a point or spot light reaches. Its inverse square falloff is smoothly
+    // windowed down to nothing at `distance`, as in glTF's KHR_lights_punctual.
+    pub fn with_range(mut self, distance: f32) -> Self {
+        match &mut self {
+            Light::Point { range, .. } | Light::Spot { range, .. } => *range = distance,
+            Light::Directional { .. } | Light::Area(_) => {}
+        }
+        self
+    }
+
+    // Shapes a point or spot light's emission by a photometric profile.
+    pub fn with_profile(mut self, ies: IesProfile) -> Self {
+        match &mut self {
+            Light::Point { profile, .. } | Light::Spot { profile, .. } => {
+                *profile = Some(Arc::new(ies));
+            }
+            Light::Directional { .. } | Light::Area(_) => {}
+        }
+        self
+    }
+
+    // Projects a texture through a spotlight, like a slide or a cut metal gobo.
+    pub fn with_gobo(mut self, texture: impl Into<Texture>) -> Self {
+        if let Light::Spot { gobo, .. } = &mut self {
+            *gobo = Some(texture.into());
+        }
+        self
+    }
+
+    // Picks a point on the light to shade `p` with. Returns None when `p` gets no light from it.
+    pub fn sample(&self, p: &Vec3) -> Option<LightSample> {
+        match self {
+            Light::Point {
+                position,
+                intensity,
+                radius,
+                range,
+                profile,
+            } => Self::sample_positional(p, position, *radius, *range, |to_p| {
+                let down = Vec3::new(0.0, -1.0, 0.0);
+                *intensity * candela(profile.as_deref(), &down, to_p)
+            }),
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                cos_inner,
+                cos_outer,
+                radius,
+                range,
+                profile,
+                gobo,
+            } => Self::sample_positional(p, position, *radius, *range, |to_p| {
+                let cos_theta = dot(direction, to_p);
+                let mut emitted = *intensity
+                    * smoothstep(*cos_outer, *cos_inner, cos_theta)
+                    * candela(profile.as_deref(), direction, to_p);
+
+                if let Some(gobo) = gobo {
+                    // Map the outer cone onto the unit square, seen from behind the light.
+                    let local = light_frame(direction).to_local(to_p);
+                    let tan_outer = cos_outer.acos().tan();
+                    if local.z() <= 0.0 || tan_outer <= 0.0 {
+                        return Vec3::default();
+                    }
+                    let s = local.x() / (local.z() * tan_outer);
+                    let t = local.y() / (local.z() * tan_outer);
+                    emitted *= gobo.value(0.5 + 0.5 * s, 0.5 + 0.5 * t, to_p);
+                }
+                emitted
+            }),
+            Light::Directional {
+                direction,
+                irradiance,
+                cos_angle,
+            } => {
+                let towards_light = if *cos_angle < 1.0 {
+                    Onb::new(&-*direction).transform(&random_in_cone(*cos_angle))
+                } else {
+                    -*direction
+                };
+                Some(LightSample {
+                    direction: towards_light.normalize(),
+                    distance: f32::INFINITY,
+                    radiance: *irradiance,
+                    pdf: 1.0,
+                })
+            }
+            Light::Area(object) => {
+                let direction = object.random(p).normalize();
+                let pdf = object.pdf_value(p, &direction);
+                let r = Ray::new(*p, direction);
+                let mut rec = HitRecord::default();
+                if pdf <= 0.0 || !object.hit(&r, &Interval::new(0.001, f32::INFINITY), &mut rec) {
+                    return None;
+                }
+
+                let radiance = rec.mat.emitted(&r, &rec);
+                if radiance == Vec3::default() {
+                    return None;
+                }
+                Some(LightSample {
+                    direction,
+                    distance: rec.t,
+                    radiance,
+                    pdf,
+                })
+            }
+        }
+    }
+
+    // Punctual lights can only be reached by sampling them.
+    pub fn is_delta(&self) -> bool {
+        !matches!(self, Light::Area(_))
+    }
+
+    // Solid angle density of `sample` picking a direction from `origin`.
+    pub fn pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        match self {
+            Light::Area(object) => object.pdf_value(origin, direction),
+            _ => 0.0,
+        }
+    }
+
+    // Rough total power, for picking lights. Directional lights cover a scene of the given radius.
+    pub fn power(&self, scene_radius: f32) -> f32 {
+        let peak = |profile: &Option<Arc<IesProfile>>| {
+            profile
+                .as_ref()
+                .map_or(1.0, |profile| profile.max_candela())
+        };
+        match self {
+            Light::Point {
+                intensity, profile, ..
+            } => 4.0 * PI * luminance(intensity) * peak(profile),
+            Light::Spot {
+                intensity,
+                cos_inner,
+                cos_outer,
+                profile,
+                ..
+            } => {
+                let solid_angle = 2.0 * PI * (1.0 - 0.5 * (cos_inner + cos_outer));
+                solid_angle * luminance(intensity) * peak(profile)
+            }
+            Light::Directional { irradiance, .. } => {
+                PI * scene_radius * scene_radius * luminance(irradiance)
+            }
+            Light::Area(object) => {
+                let emission = object.material().map_or(Vec3::default(), |m| m.emission());
+                PI * object.area() * luminance(&emission)
+            }
+        }
+    }
+
+    // Region the light is in, or None for directional lights.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let around = |position: &Vec3, radius: f32| {
+            let extent = Vec3::new(radius, radius, radius);
+            Aabb::from_points(*position - extent, *position + extent)
+        };
+        match self {
+            Light::Point {
+                position, radius, ..
+            }
+            | Light::Spot {
+                position, radius, ..
+            } => Some(around(position, *radius)),
+            Light::Directional { .. } => None,
+            Light::Area(object) => Some(object.bounding_box()),
+        }
+    }
+
+    // Shared by point and spot lights, with `intensity` giving the emission along a unit direction
+    // from the light.
+    fn sample_positional(
+        p: &Vec3,
+        position: &Vec3,
+        radius: f32,
+        range: f32,
+        intensity: impl Fn(&Vec3) -> Vec3,
+    ) -> Option<LightSample> {
+        // Spread the light uniformly through a ball for soft shadows.
+        let light_point = if radius > 0.0 {
+            *position + radius * rand_f32().cbrt() * random_vec()
+        } else {
+            *position
+        };
+
+        let offset = light_point - *p;
+        let distance_squared = offset.length_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = offset / distance;
+
+        let window = (1.0 - (distance / range).powi(4)).clamp(0.0, 1.0).powi(2);
+        let radiance = intensity(&-direction) * window / distance_squared;
+        if radiance == Vec3::default() {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance,
+            pdf: 1.0,
+        })
+    }
+}
+
+// Frame with the nadir of a profile along `w` and horizontal angles starting from `u`.
+fn light_frame(nadir: &Vec3) -> Onb {
+    Onb::from_normal_tangent(nadir, &Vec3::new(1.0, 0.0, 0.0))
+}
+
+// Profile intensity in a unit direction from the light, or one without a profile.
+fn candela(profile: Option<&IesProfile>, nadir: &Vec3, direction: &Vec3) -> f32 {
+    let Some(profile) = profile else {
+        return 1.0;
+    };
+    let local = light_frame(nadir).to_local(direction);
+    let theta = local.z().clamp(-1.0, 1.0).acos().to_degrees();
+    let phi = local.y().atan2(local.x()).to_degrees();
+    profile.candela(theta, phi)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge0 >= edge1 {
+        return if x >= edge1 { 1.0 } else { 0.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[test]
+fn test_punctual_lights() {
+    let p = Vec3::new(0.0, 0.0, 0.0);
+
+    let point = Light::point(Vec3::new(0.0, 2.0, 0.0), Vec3::new(4.0, 4.0, 4.0));
+    let sample = point.sample(&p).unwrap();
+    assert!((sample.direction - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-6);
+    assert_eq!(sample.distance, 2.0);
+    assert_eq!(sample.radiance, Vec3::new(1.0, 1.0, 1.0));
+
+    // A range fades the light out, leaving it alone close up.
+    let ranged = point.clone().with_range(4.0);
+    let faded = ranged.sample(&p).unwrap().radiance.x();
+    assert!((faded - (1.0 - 1.0 / 16.0_f32).powi(2)).abs() < 1e-6);
+    assert!(ranged.sample(&Vec3::new(0.0, -2.5, 0.0)).is_none());
+    let near = Vec3::new(0.0, 1.9, 0.0);
+    assert!(
+        (ranged.sample(&near).unwrap().radiance - point.sample(&near).unwrap().radiance).length()
+            < 1e-2
+    );
+
+    // Full intensity inside the inner cone, none outside the outer one.
+    let white = Vec3::new(1.0, 1.0, 1.0);
+    let spot = Light::spot(p, Vec3::new(0.0, -1.0, 0.0), white, 20.0, 30.0);
+    let radiance = |q: Vec3| spot.sample(&q).map_or(0.0, |s| s.radiance.x());
+    assert_eq!(radiance(Vec3::new(0.0, -1.0, 0.0)), 1.0);
+    assert_eq!(radiance(Vec3::new(1.0, -1.0, 0.0)), 0.0);
+    let edge = radiance(Vec3::new(0.45, -1.0, 0.0));
+    assert!(edge > 0.0 && edge < 1.0 / 1.2);
+
+    // Profiles and gobos shape the light.
+    let ies =
+        IesProfile::parse("TILT=NONE\n1 1000 1 2 1 1 2 0 0 0\n1 1 10\n0 90\n0\n8 0\n").unwrap();
+    let downlight = Light::point(p, white).with_profile(ies);
+    let below = downlight.sample(&Vec3::new(0.0, -2.0, 0.0)).unwrap();
+    assert_eq!(below.radiance, Vec3::new(2.0, 2.0, 2.0));
+    assert!(downlight.sample(&Vec3::new(0.0, 2.0, 0.0)).is_none());
+
+    let checker = Texture::checker(0.5, Vec3::default().into(), white.into());
+    let slide = Light::spot(p, Vec3::new(0.0, -1.0, 0.0), white, 30.0, 30.0).with_gobo(checker);
+    let lit = [-0.2, 0.2].map(|x| slide.sample(&Vec3::new(x, -1.0, 0.0)).is_some());
+    assert_eq!(lit, [true, false]);
+
+    // Soft directional light stays within its cone.
+    let sun =
+        Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::new(3.0, 3.0, 3.0)).with_radius(5.0);
+    let sample = sun.sample(&p).unwrap();
+    assert!(sample.direction.y() >= 5.0_f32.to_radians().cos() - 1e-6);
+    assert!(sample.distance.is_infinite());
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use crate::{
+    aabb::Aabb,
+    hittable::HitRecord,
+    hittable_list::ObjectKey,
+    light::Light,
+    ray::Ray,
+    sampling::AliasTable,
+    util::{rand_f32, rand_index},
+    Vec3,
+};
+
+// Picks one light to sample at each shading point, either in proportion to the lights' power or,
+// with a light BVH, by their estimated contribution to the point. It also finds the light a BSDF
+// sampled ray hit, to weigh the two strategies against each other.
+#[derive(Default)]
+pub struct LightSampler {
+    lights: Vec<Light>,
+    power: AliasTable,
+    use_bvh: bool,
+    nodes: Vec<LightNode>,
+    infinite: Vec<usize>, // Directional lights, which can't go in the BVH
+    trails: Vec<u64>,     // Path from the root to each bounded light, one bit per level
+    emitters: HashMap<ObjectKey, usize>, // Area lights by the object they light up
+}
+
+#[derive(Clone, Copy)]
+struct LightNode {
+    bounds: Aabb,
+    power: f32,
+    second_child: usize, // The first child follows its parent
+    light: Option<usize>,
+}
+
+impl LightSampler {
+    pub fn new(lights: Vec<Light>, use_bvh: bool, scene_radius: f32) -> Self {
+        let power: Vec<f32> = lights.iter().map(|l| l.power(scene_radius)).collect();
+        let mut sampler = LightSampler {
+            power: AliasTable::new(&power),
+            use_bvh,
+            trails: vec![0; lights.len()],
+            ..Default::default()
+        };
+
+        let mut bounded = Vec::new();
+        for (i, light) in lights.iter().enumerate() {
+            match light.bounds() {
+                Some(bounds) => bounded.push((i, bounds, power[i])),
+                None => sampler.infinite.push(i),
+            }
+        }
+        if !bounded.is_empty() {
+            sampler.build(&mut bounded, 0, 0);
+        }
+
+        sampler.emitters = lights
+            .iter()
+            .enumerate()
+            .filter_map(|(i, light)| match light {
+                Light::Area(object) => Some((object.key()?, i)),
+                _ => None,
+            })
+            .collect();
+        sampler.lights = lights;
+        sampler
+    }
+
+    // Adds a subtree over `lights` and returns the index of its root, splitting at the median
+    // along the longest axis of the lights' centres.
+    fn build(&mut self, lights: &mut [(usize, Aabb, f32)], trail: u64, depth: u32) -> usize {
+        let index = self.nodes.len();
+        let bounds = lights
+            .iter()
+            .fold(Aabb::EMPTY, |b, (_, light, _)| Aabb::surrounding(&b, light));
+        let power = lights.iter().map(|(_, _, p)| p).sum();
+        self.nodes.push(LightNode {
+            bounds,
+            power,
+            second_child: 0,
+            light: None,
+        });
+
+        if let [(light, _, _)] = lights {
+            self.nodes[index].light = Some(*light);
+            self.trails[*light] = trail;
+            return index;
+        }
+
+        let centres = lights.iter().fold(Aabb::EMPTY, |b, (_, light, _)| {
+            let c = light.centroid();
+            Aabb::surrounding(&b, &Aabb::from_points(c, c))
+        });
+        let axis = centres.longest_axis();
+        lights.sort_by(|a, b| a.1.centroid()[axis].total_cmp(&b.1.centroid()[axis]));
+
+        let (first, second) = lights.split_at_mut(lights.len() / 2);
+        self.build(first, trail, depth + 1);
+        self.nodes[index].second_child = self.build(second, trail | (1 << depth), depth + 1);
+        index
+    }
+
+    // Estimated contribution of a node's lights to a point, falling off with distance but not
+    // growing without bound inside the node.
+    fn importance(node: &LightNode, p: &Vec3) -> f32 {
+        let centre = node.bounds.centroid();
+        let half_diagonal = Vec3::new(
+            node.bounds.x.size(),
+            node.bounds.y.size(),
+            node.bounds.z.size(),
+        ) * 0.5;
+        let distance_squared = (*p - centre)
+            .length_squared()
+            .max(half_diagonal.length_squared());
+        node.power / distance_squared
+    }
+
+    // Chance of picking the BVH rather than one of the directional lights.
+    #[allow(clippy::cast_precision_loss)]
+    fn bvh_probability(&self) -> f32 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        1.0 / (1.0 + self.infinite.len() as f32)
+    }
+
+    // Picks a light for shading `p`, returning it with its probability.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample(&self, p: &Vec3) -> Option<(&Light, f32)> {
+        if !self.use_bvh {
+            let (index, pmf) = self.power.sample(rand_f32())?;
+            return (pmf > 0.0).then(|| (&self.lights[index], pmf));
+        }
+
+        let p_bvh = self.bvh_probability();
+        if rand_f32() >= p_bvh {
+            if self.infinite.is_empty() {
+                return None;
+            }
+            let index = self.infinite[rand_index(self.infinite.len())];
+            return Some((
+                &self.lights[index],
+                (1.0 - p_bvh) / self.infinite.len() as f32,
+            ));
+        }
+
+        let mut node = 0;
+        let mut pmf = p_bvh;
+        loop {
+            if let Some(light) = self.nodes[node].light {
+                return Some((&self.lights[light], pmf));
+            }
+            let (first, second) = (node + 1, self.nodes[node].second_child);
+            let w0 = Self::importance(&self.nodes[first], p);
+            let w1 = Self::importance(&self.nodes[second], p);
+            if w0 + w1 <= 0.0 {
+                return None;
+            }
+            let p0 = w0 / (w0 + w1);
+            if rand_f32() < p0 {
+                node = first;
+                pmf *= p0;
+            } else {
+                node = second;
+                pmf *= 1.0 - p0;
+            }
+        }
+    }
+
+    // Probability of `sample` picking light `index` for shading `p`.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn pmf(&self, p: &Vec3, index: usize) -> f32 {
+        if !self.use_bvh {
+            return self.power.pmf(index);
+        }
+        if self.lights[index].bounds().is_none() {
+            return (1.0 - self.bvh_probability()) / self.infinite.len() as f32;
+        }
+
+        let trail = self.trails[index];
+        let mut node = 0;
+        let mut pmf = self.bvh_probability();
+        let mut depth = 0;
+        while self.nodes[node].light.is_none() {
+            let (first, second) = (node + 1, self.nodes[node].second_child);
+            let w0 = Self::importance(&self.nodes[first], p);
+            let w1 = Self::importance(&self.nodes[second], p);
+            if w0 + w1 <= 0.0 {
+                return 0.0;
+            }
+            if trail & (1 << depth) == 0 {
+                node = first;
+                pmf *= w0 / (w0 + w1);
+            } else {
+                node = second;
+                pmf *= w1 / (w0 + w1);
+            }
+            depth += 1;
+        }
+        pmf
+    }
+
+    // Density of light sampling from the origin of `r` reaching the emitter it hit at `rec`.
+    pub fn pdf(&self, r: &Ray, rec: &HitRecord) -> f32 {
+        let Some(index) = self.find(rec) else {
+            return 0.0;
+        };
+        self.pmf(r.origin(), index) * self.lights[index].pdf(r.origin(), r.direction())
+    }
+
+    // The area light whose object was hit at `rec`, if any.
+    fn find(&self, rec: &HitRecord) -> Option<usize> {
+        let key = rec.object?.key()?;
+        self.emitters.get(&key).copied()
+    }
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_light_sampler() {
+    use crate::{
+        bvh::BvhNode,
+        hittable::Hittable,
+        hittable_list::{HittableList, HittableObject},
+        interval::Interval,
+        material::Material,
+        sphere::Sphere,
+    };
+
+    let emitter = |x: f32, emit: f32| {
+        let emit = Vec3::new(emit, emit, emit);
+        let sphere = Sphere::new(Vec3::new(x, 0.0, 0.0), 0.5, Material::DiffuseLight { emit });
+        Light::area(HittableObject::Sphere(sphere))
+    };
+    let lights = vec![
+        emitter(-10.0, 1.0),
+        emitter(10.0, 1.0),
+        emitter(20.0, 2.0),
+        Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 1.0)),
+    ];
+
+    for use_bvh in [false, true] {
+        let sampler = LightSampler::new(lights.clone(), use_bvh, 10.0);
+        let p = Vec3::new(-9.0, 0.0, 0.0);
+
+        // Probabilities add up and match what sampling picks.
+        let total: f32 = (0..lights.len()).map(|i| sampler.pmf(&p, i)).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        for _ in 0..50 {
+            let (light, pmf) = sampler.sample(&p).unwrap();
+            let index = (0..lights.len())
+                .find(|&i| std::ptr::eq(&sampler.lights[i], light))
+                .unwrap();
+            assert!((sampler.pmf(&p, index) - pmf).abs() < 1e-6);
+        }
+
+        // A ray hitting an emitter in the world, a copy of the light's object, finds its light.
+        let mut world = HittableList::default();
+        for light in &lights {
+            if let Light::Area(object) = light {
+                world.add(object.clone());
+            }
+        }
+        let world = BvhNode::new(world);
+        let r = Ray::new(p, Vec3::new(1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(world.hit(&r, &Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert_eq!(sampler.find(&rec), Some(1));
+
+        // The BVH prefers the nearby light over the brighter distant one.
+        if use_bvh {
+            assert!(sampler.pmf(&p, 0) > sampler.pmf(&p, 2));
+        } else {
+            assert!(sampler.pmf(&p, 0) < sampler.pmf(&p, 2));
+        }
+    }
+}
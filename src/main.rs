@@ -1,22 +1,15 @@
-mod camera;
-mod color;
-mod hittable;
-mod hittable_list;
-mod interval;
-mod material;
-mod ray;
-mod sphere;
-mod util;
-mod vec3;
-use camera::Camera;
-use hittable_list::{HittableList, HittableObject};
-use material::Material;
-use sphere::Sphere;
-use util::{rand, rand_f32};
-use vec3::{random_range, random_vec, Vec3};
+use raytracing::{
+    bvh::BvhNode,
+    camera::Camera,
+    hittable_list::{HittableList, HittableObject},
+    material::Material,
+    sphere::Sphere,
+    util::{rand, rand_f32},
+    vec3::{random_range, random_vec, Vec3},
+};
 
 #[allow(clippy::cast_precision_loss)]
-fn main() {
+fn main() -> std::io::Result<()> {
     let mut world = HittableList::default();
 
     let ground_material = Material::Lambartian {
@@ -108,6 +101,8 @@ fn main() {
         material3,
     )));
 
+    let world = BvhNode::new(world);
+
     let mut cam = Camera::default();
     cam.aspect_ratio = 16.0 / 9.0;
     // High quality
@@ -130,5 +125,5 @@ fn main() {
     cam.defocus_angle = 0.6;
     cam.focus_dist = 10.0;
 
-    cam.render(&world);
+    cam.render(&world)
 }
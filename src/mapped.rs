@@ -0,0 +1,136 @@
+use crate::{
+    bsdf::{Bsdf, BsdfSample},
+    hittable::HitRecord,
+    material::Material,
+    medium::Medium,
+    ray::Ray,
+    texture::Texture,
+    vec3::{cross, dot, Vec3},
+};
+
+// Step in surface coordinates for differencing height maps.
+const BUMP_DELTA: f32 = 1e-3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SurfaceMap {
+    // Tangent space normal map, with colours in [0, 1] encoding components in [-1, 1]. Load
+    // images for it with `Image::load_data` so they aren't gamma decoded.
+    Normal(Texture),
+    // Height map displacing the surface along its normal by `scale` world units per unit of height.
+    Bump { height: Texture, scale: f32 },
+}
+
+// Adds surface detail to any material by perturbing its shading normal with a normal or bump map.
+#[derive(Clone)]
+pub struct Mapped {
+    pub base: Material,
+    pub map: SurfaceMap,
+}
+
+impl Mapped {
+    pub fn normal_map(base: Material, normals: impl Into<Texture>) -> Self {
+        Mapped {
+            base,
+            map: SurfaceMap::Normal(normals.into()),
+        }
+    }
+
+    pub fn bump_map(base: Material, height: impl Into<Texture>, scale: f32) -> Self {
+        Mapped {
+            base,
+            map: SurfaceMap::Bump {
+                height: height.into(),
+                scale,
+            },
+        }
+    }
+
+    // The hit record with the perturbed shading frame the base material sees.
+    fn shade<'a>(&self, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        // Work with the outward facing frame so maps look the same from both sides.
+        let sign = if rec.front_face { 1.0 } else { -1.0 };
+        let frame = rec.frame();
+        let tangent = *frame.u();
+        let bitangent = sign * *frame.v();
+        let normal = sign * *frame.w();
+
+        let shading_normal = match &self.map {
+            SurfaceMap::Normal(texture) => {
+                let c = texture.value(rec.u, rec.v, &rec.p) * 2.0 - Vec3::new(1.0, 1.0, 1.0);
+                c.x() * tangent + c.y() * bitangent + c.z() * normal
+            }
+            SurfaceMap::Bump { height, scale } => {
+                if rec.dpdu.length_squared() == 0.0 || rec.dpdv.length_squared() == 0.0 {
+                    return *rec;
+                }
+                let h = |u: f32, v: f32, p: &Vec3| {
+                    let value = height.value(u, v, p);
+                    scale * (value.x() + value.y() + value.z()) / 3.0
+                };
+
+                // Forward differences of the displaced surface along u and v.
+                let h0 = h(rec.u, rec.v, &rec.p);
+                let hu = h(rec.u + BUMP_DELTA, rec.v, &(rec.p + BUMP_DELTA * rec.dpdu));
+                let hv = h(rec.u, rec.v + BUMP_DELTA, &(rec.p + BUMP_DELTA * rec.dpdv));
+                let dpdu = rec.dpdu + ((hu - h0) / BUMP_DELTA) * normal;
+                let dpdv = rec.dpdv + ((hv - h0) / BUMP_DELTA) * normal;
+
+                let n = cross(&dpdu, &dpdv);
+                if dot(&n, &normal) < 0.0 {
+                    -n
+                } else {
+                    n
+                }
+            }
+        };
+
+        let mut shaded = *rec;
+        if shading_normal.length_squared() > 0.0 {
+            shaded.set_shading(rec.dpdu, rec.dpdv, shading_normal);
+        }
+        shaded
+    }
+}
+
+impl Bsdf for Mapped {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.base.sample(r_in, &self.shade(rec))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        self.base.eval(r_in, &self.shade(rec), direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        self.base.pdf(r_in, &self.shade(rec), direction)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        self.base.emitted(r_in, &self.shade(rec))
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        self.base.interior()
+    }
+}
+
+#[test]
+fn test_surface_maps() {
+    let mut rec = HitRecord {
+        front_face: false,
+        ..Default::default()
+    };
+    let outward = Vec3::new(0.0, 0.0, 1.0);
+    rec.set_shading(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), outward);
+
+    // A flat normal map and a constant height leave the normal alone.
+    let flat = Mapped::normal_map(Material::default(), Vec3::new(0.5, 0.5, 1.0));
+    assert!((flat.shade(&rec).normal - rec.normal).length() < 1e-5);
+    let constant = Mapped::bump_map(Material::default(), 0.7, 2.0);
+    assert!((constant.shade(&rec).normal - rec.normal).length() < 1e-5);
+
+    // Tilting towards the tangent looks the same from either side of the surface.
+    let tilted = Mapped::normal_map(Material::default(), Vec3::new(1.0, 0.5, 1.0));
+    let expected = -Vec3::new(1.0, 0.0, 1.0).normalize();
+    assert!((tilted.shade(&rec).normal - expected).length() < 1e-5);
+}
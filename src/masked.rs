@@ -0,0 +1,96 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableObject,
+    interval::Interval,
+    ray::Ray,
+    texture::Texture,
+    util::rand_f32,
+    Vec3,
+};
+
+// An object with parts cut away by an alpha texture, for leaves and fences modelled as textured
+// quads. Rays, shadow rays included, pass through where the alpha is zero and fractional alpha
+// is treated as the probability of a hit.
+#[derive(Clone)]
+pub struct Masked {
+    pub object: HittableObject,
+    pub alpha: Texture,
+}
+
+impl Masked {
+    // Opacity at a hit, the average of the texture's channels.
+    fn alpha(&self, rec: &HitRecord) -> f32 {
+        let value = self.alpha.value(rec.u, rec.v, &rec.p);
+        (value.x() + value.y() + value.z()) / 3.0
+    }
+}
+
+impl Hittable for Masked {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        // Only touch `rec` for an accepted hit, since callers keep it for closer hits.
+        let mut temp_record = HitRecord::default();
+        let mut ray_t = *ray_t;
+
+        // Keep looking past masked out hits until an opaque one or a miss.
+        while self.object.hit(r, &ray_t, &mut temp_record) {
+            let alpha = self.alpha(&temp_record);
+            if alpha >= 1.0 || (alpha > 0.0 && rand_f32() < alpha) {
+                *rec = temp_record;
+                return true;
+            }
+            // Objects that report hits at the start of the interval would never move on.
+            if temp_record.t <= ray_t.min {
+                return false;
+            }
+            ray_t = Interval::new(temp_record.t, ray_t.max);
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        self.object.random(origin)
+    }
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_alpha_mask() {
+    use crate::{material::Material, quad::Quad, sphere::Sphere};
+
+    let quad = HittableObject::Quad(Quad::new(
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 2.0, 0.0),
+        Material::default(),
+    ));
+    let r = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    let interval = Interval::new(0.001, f32::INFINITY);
+    let mut rec = HitRecord::default();
+
+    let (opaque, clear) = (quad.clone().with_alpha(1.0), quad.clone().with_alpha(0.0));
+    assert!(opaque.hit(&r, &interval, &mut rec));
+    assert!(!clear.hit(&r, &interval, &mut HitRecord::default()));
+
+    // Fractional alpha lets the matching fraction of rays through.
+    let half = quad.with_alpha(0.25);
+    let n = 4000;
+    let hits = (0..n).filter(|_| half.hit(&r, &interval, &mut rec)).count();
+    assert!((hits as f32 / n as f32 - 0.25).abs() < 0.03);
+
+    // Masking the front of a sphere reveals its back face.
+    let sphere =
+        HittableObject::Sphere(Sphere::new(Vec3::default(), 1.0, Material::default())).with_alpha(
+            Texture::checker(0.7, Vec3::new(1.0, 1.0, 1.0).into(), Vec3::default().into()),
+        );
+    assert!(sphere.hit(&r, &interval, &mut rec));
+    assert!(!rec.front_face && (rec.t - 3.0).abs() < 1e-4);
+}
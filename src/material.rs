@@ -1,82 +1,179 @@
+use std::{f32::consts::PI, sync::Arc};
+
 use crate::{
+    bsdf::{Bsdf, BsdfSample, Lobe},
+    conductor::Conductor,
+    dielectric::{RoughDielectric, ThinDielectric},
+    diffuse::{DiffuseTransmission, OrenNayar},
     hittable::HitRecord,
+    layered::{Layered, Mix},
+    mapped::Mapped,
+    medium::Medium,
+    principled::Principled,
     ray::Ray,
+    subsurface::Subsurface,
     util::rand_f32,
-    vec3::{dot, random_vec, reflect, refract, Vec3},
+    vec3::{dot, random_cosine_direction, random_vec, reflect, refract, Vec3},
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum Material {
     Lambartian { albedo: Vec3 },
     Metal { albedo: Vec3, fuzz: f32 },
     // Refractive index in vacuum of air
     // Or the ratio of the refractive index over the refractive index of the enclosing media
     Dialetric { refraction_index: f32 },
+    // Emits light from its front face and reflects nothing.
+    DiffuseLight { emit: Vec3 },
+    Conductor(Conductor),
+    RoughDielectric(RoughDielectric),
+    ThinDielectric(ThinDielectric),
+    Principled(Arc<Principled>),
+    Layered(Arc<Layered>),
+    Mix(Arc<Mix>),
+    OrenNayar(OrenNayar),
+    DiffuseTransmission(DiffuseTransmission),
+    Subsurface(Arc<Subsurface>),
+    Mapped(Arc<Mapped>),
+    Custom(Arc<dyn Bsdf>),
 }
 
-impl Material {
-    pub fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
+impl Bsdf for Material {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         match self {
-            Material::Lambartian { albedo } => {
-                Self::scatter_lambartian(*albedo, r_in, rec, attenuation, scattered)
+            Material::Lambartian { albedo } => Self::sample_lambartian(r_in.spectrum(albedo), rec),
+            Material::Metal { albedo, fuzz } => {
+                Self::sample_metal(r_in.spectrum(albedo), *fuzz, r_in, rec)
+            }
+            Material::Dialetric { refraction_index } => {
+                Self::sample_dialetric(*refraction_index, r_in, rec)
             }
+            Material::DiffuseLight { .. } => None,
+            Material::Conductor(conductor) => conductor.sample(r_in, rec),
+            Material::RoughDielectric(dielectric) => dielectric.sample(r_in, rec),
+            Material::ThinDielectric(dielectric) => dielectric.sample(r_in, rec),
+            Material::Principled(principled) => principled.sample(r_in, rec),
+            Material::Layered(layered) => layered.sample(r_in, rec),
+            Material::Mix(mix) => mix.sample(r_in, rec),
+            Material::OrenNayar(oren_nayar) => oren_nayar.sample(r_in, rec),
+            Material::DiffuseTransmission(diffuse) => diffuse.sample(r_in, rec),
+            Material::Subsurface(subsurface) => subsurface.sample(r_in, rec),
+            Material::Mapped(mapped) => mapped.sample(r_in, rec),
+            Material::Custom(bsdf) => bsdf.sample(r_in, rec),
+        }
+    }
 
-            Material::Metal { albedo, fuzz } => {
-                Self::scatter_metal(*albedo, *fuzz, *r_in, *rec, attenuation, scattered)
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        match self {
+            Material::Lambartian { albedo } => {
+                let cosine = dot(&rec.normal, &direction.normalize());
+                if cosine <= 0.0 {
+                    return Vec3::default();
+                }
+                r_in.spectrum(albedo) * (cosine / PI)
+            }
+            Material::Metal { .. } | Material::Dialetric { .. } | Material::DiffuseLight { .. } => {
+                Vec3::default()
             }
+            Material::Conductor(conductor) => conductor.eval(r_in, rec, direction),
+            Material::RoughDielectric(dielectric) => dielectric.eval(r_in, rec, direction),
+            Material::ThinDielectric(dielectric) => dielectric.eval(r_in, rec, direction),
+            Material::Principled(principled) => principled.eval(r_in, rec, direction),
+            Material::Layered(layered) => layered.eval(r_in, rec, direction),
+            Material::Mix(mix) => mix.eval(r_in, rec, direction),
+            Material::OrenNayar(oren_nayar) => oren_nayar.eval(r_in, rec, direction),
+            Material::DiffuseTransmission(diffuse) => diffuse.eval(r_in, rec, direction),
+            Material::Subsurface(subsurface) => subsurface.eval(r_in, rec, direction),
+            Material::Mapped(mapped) => mapped.eval(r_in, rec, direction),
+            Material::Custom(bsdf) => bsdf.eval(r_in, rec, direction),
+        }
+    }
 
-            Material::Dialetric { refraction_index } => {
-                Self::scatter_dialetric(*refraction_index, *r_in, *rec, attenuation, scattered)
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        match self {
+            Material::Lambartian { .. } => dot(&rec.normal, &direction.normalize()).max(0.0) / PI,
+            Material::Metal { .. } | Material::Dialetric { .. } | Material::DiffuseLight { .. } => {
+                0.0
             }
+            Material::Conductor(conductor) => conductor.pdf(r_in, rec, direction),
+            Material::RoughDielectric(dielectric) => dielectric.pdf(r_in, rec, direction),
+            Material::ThinDielectric(dielectric) => dielectric.pdf(r_in, rec, direction),
+            Material::Principled(principled) => principled.pdf(r_in, rec, direction),
+            Material::Layered(layered) => layered.pdf(r_in, rec, direction),
+            Material::Mix(mix) => mix.pdf(r_in, rec, direction),
+            Material::OrenNayar(oren_nayar) => oren_nayar.pdf(r_in, rec, direction),
+            Material::DiffuseTransmission(diffuse) => diffuse.pdf(r_in, rec, direction),
+            Material::Subsurface(subsurface) => subsurface.pdf(r_in, rec, direction),
+            Material::Mapped(mapped) => mapped.pdf(r_in, rec, direction),
+            Material::Custom(bsdf) => bsdf.pdf(r_in, rec, direction),
         }
     }
 
-    fn scatter_lambartian(
-        albedo: Vec3,
-        _r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + random_vec();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        match self {
+            Material::DiffuseLight { emit } if rec.front_face => r_in.spectrum(emit),
+            Material::Principled(principled) => principled.emitted(r_in, rec),
+            Material::Layered(layered) => layered.emitted(r_in, rec),
+            Material::Mix(mix) => mix.emitted(r_in, rec),
+            Material::Mapped(mapped) => mapped.emitted(r_in, rec),
+            Material::Custom(bsdf) => bsdf.emitted(r_in, rec),
+            _ => Vec3::default(),
         }
+    }
 
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = albedo;
-        true
+    fn interior(&self) -> Option<Medium> {
+        match self {
+            Material::Subsurface(subsurface) => subsurface.interior(),
+            Material::Mapped(mapped) => mapped.interior(),
+            Material::Custom(bsdf) => bsdf.interior(),
+            _ => None,
+        }
     }
+}
+
+impl Material {
+    // Rough emitted radiance in RGB, for picking lights by their power. Custom materials don't
+    // report any, so they're only found by BSDF sampling.
+    pub fn emission(&self) -> Vec3 {
+        match self {
+            Material::DiffuseLight { emit } => *emit,
+            Material::Principled(principled) => principled.emission,
+            Material::Layered(layered) => layered.base.emission(),
+            Material::Mix(mix) => mix.emission(),
+            Material::Mapped(mapped) => mapped.base.emission(),
+            _ => Vec3::default(),
+        }
+    }
+
+    fn sample_lambartian(albedo: Vec3, rec: &HitRecord) -> Option<BsdfSample> {
+        let uvw = rec.frame();
+        let local = random_cosine_direction();
 
-    fn scatter_metal(
-        albedo: Vec3,
-        fuzz: f32,
-        r_in: Ray,
-        rec: HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
+        Some(BsdfSample {
+            direction: uvw.transform(&local),
+            weight: albedo,
+            pdf: local.z() / PI,
+            lobe: Lobe::DIFFUSE | Lobe::REFLECTION,
+        })
+    }
+
+    fn sample_metal(albedo: Vec3, fuzz: f32, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        // The fuzzed reflection has no closed form density, so it is treated as a specular lobe.
         let mut reflected = reflect(r_in.direction(), &rec.normal);
         reflected = reflected.normalize() + (fuzz * random_vec());
-        *scattered = Ray::new(rec.p, reflected);
-        *attenuation = albedo;
-        dot(scattered.direction(), &rec.normal) > 0.0
+        if dot(&reflected, &rec.normal) <= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            direction: reflected,
+            weight: albedo,
+            pdf: 1.0,
+            lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+        })
     }
 
-    fn scatter_dialetric(
-        refraction_index: f32,
-        r_in: Ray,
-        rec: HitRecord,
-        attenuation: &mut Vec3,
-        scattered: &mut Ray,
-    ) -> bool {
-        *attenuation = Vec3::new(1.0, 1.0, 1.0);
+    fn sample_dialetric(refraction_index: f32, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let ri = if rec.front_face {
             1.0 / refraction_index
         } else {
@@ -87,14 +184,32 @@ impl Material {
         let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
 
         let cannot_refract = ri * sin_theta > 1.0;
+        let reflect_probability = if cannot_refract {
+            1.0
+        } else {
+            reflectance(cos_theta, ri)
+        };
 
-        let direction = if cannot_refract || reflectance(cos_theta, ri) > rand_f32() {
-            reflect(&unit_direction, &rec.normal)
+        let (direction, pdf, lobe) = if reflect_probability > rand_f32() {
+            (
+                reflect(&unit_direction, &rec.normal),
+                reflect_probability,
+                Lobe::REFLECTION,
+            )
         } else {
-            refract(&unit_direction, &rec.normal, ri)
+            (
+                refract(&unit_direction, &rec.normal, ri),
+                1.0 - reflect_probability,
+                Lobe::TRANSMISSION,
+            )
         };
-        *scattered = Ray::new(rec.p, direction);
-        true
+
+        Some(BsdfSample {
+            direction,
+            weight: Vec3::new(1.0, 1.0, 1.0),
+            pdf,
+            lobe: Lobe::SPECULAR | lobe,
+        })
     }
 }
 
@@ -112,3 +227,25 @@ impl Default for Material {
         }
     }
 }
+
+#[test]
+fn test_lambartian_sample_matches_eval() {
+    let mat = Material::Lambartian {
+        albedo: Vec3::new(0.8, 0.5, 0.2),
+    };
+    let rec = HitRecord {
+        normal: Vec3::new(0.0, 1.0, 0.0),
+        front_face: true,
+        ..Default::default()
+    };
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+
+    for _ in 0..100 {
+        let srec = mat.sample(&r_in, &rec).unwrap();
+        let pdf = mat.pdf(&r_in, &rec, &srec.direction);
+        let expected = mat.eval(&r_in, &rec, &srec.direction) / pdf;
+
+        assert!((pdf - srec.pdf).abs() < 1e-4);
+        assert!((expected - srec.weight).length() < 1e-3);
+    }
+}
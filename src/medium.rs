@@ -0,0 +1,105 @@
+use std::f32::consts::PI;
+
+use crate::{
+    onb::Onb,
+    ray::Ray,
+    util::rand_f32,
+    vec3::{dot, Vec3},
+};
+
+// Homogeneous participating medium with per channel coefficients.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Medium {
+    pub sigma_a: Vec3, // Absorption coefficient per unit distance
+    pub sigma_s: Vec3, // Scattering coefficient per unit distance
+    pub g: f32,        // Henyey-Greenstein anisotropy, from -1 (backward) to 1 (forward)
+}
+
+pub enum MediumSample {
+    // The ray scattered inside the medium at parameter `t`.
+    Scatter { t: f32, weight: Vec3 },
+    // The ray reached the end of the segment without scattering.
+    Pass { weight: Vec3 },
+}
+
+impl Medium {
+    pub fn sigma_t(&self) -> Vec3 {
+        self.sigma_a + self.sigma_s
+    }
+
+    // Samples a free-flight distance along `r` up to the parameter `t_max`. Each channel has its
+    // own extinction, so a channel is picked in proportion to the path `throughput` and the weight
+    // uses the pdf averaged over all channels. In spectral mode the channels are the ray's
+    // wavelengths.
+    pub fn sample(&self, r: &Ray, t_max: f32, throughput: &Vec3) -> MediumSample {
+        let sigma_s = r.interpolate(&self.sigma_s);
+        let sigma_t = r.interpolate(&self.sigma_a) + sigma_s;
+        let speed = r.direction().length();
+        let max_distance = t_max * speed;
+
+        let total = throughput.x() + throughput.y() + throughput.z();
+        let probabilities = if total > 0.0 {
+            *throughput / total
+        } else {
+            Vec3::new(1.0, 1.0, 1.0) / 3.0
+        };
+        let u = rand_f32();
+        let channel = if u < probabilities.x() {
+            0
+        } else if u < probabilities.x() + probabilities.y() {
+            1
+        } else {
+            2
+        };
+
+        let distance = if sigma_t[channel] > 0.0 {
+            -f32::ln(1.0 - rand_f32()) / sigma_t[channel]
+        } else {
+            f32::INFINITY
+        };
+
+        let scatter = distance < max_distance;
+        let distance = distance.min(max_distance);
+        let transmittance = Vec3::new(
+            f32::exp(-sigma_t.x() * distance),
+            f32::exp(-sigma_t.y() * distance),
+            f32::exp(-sigma_t.z() * distance),
+        );
+
+        if scatter {
+            let pdf = dot(&probabilities, &(sigma_t * transmittance));
+            MediumSample::Scatter {
+                t: distance / speed,
+                weight: sigma_s * transmittance / pdf,
+            }
+        } else {
+            let pdf = dot(&probabilities, &transmittance);
+            MediumSample::Pass {
+                weight: transmittance / pdf,
+            }
+        }
+    }
+
+    // Samples a new propagation direction from the Henyey-Greenstein phase function. Since the
+    // phase function is sampled exactly the scattering weight is unaffected.
+    pub fn sample_phase(&self, direction: &Vec3) -> Vec3 {
+        let g = self.g;
+        let u = rand_f32();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let sqr_term = (1.0 - g * g) / (1.0 + g - 2.0 * g * u);
+            (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        }
+        .clamp(-1.0, 1.0);
+
+        let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
+        let phi = 2.0 * PI * rand_f32();
+        let uvw = Onb::new(direction);
+        uvw.transform(&Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
@@ -0,0 +1,341 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableObject,
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    util::rand_f32,
+    vec3::{cross, dot, Vec3},
+};
+
+// Indexed triangle mesh with counter-clockwise winding for the outside. Vertex normals give smooth
+// shading and vertex uvs place textures; without them triangles are flat shaded and each gets the
+// whole unit square.
+pub struct TriangleMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,   // Empty, or one per position
+    pub uvs: Vec<(f32, f32)>, // Empty, or one per position
+    pub indices: Vec<[usize; 3]>,
+    pub mat: Material,
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[usize; 3]>, mat: Material) -> Self {
+        assert!(indices.iter().flatten().all(|&i| i < positions.len()));
+        TriangleMesh {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices,
+            mat,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(normals.len(), self.positions.len());
+        self.normals = normals;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<(f32, f32)>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len());
+        self.uvs = uvs;
+        self
+    }
+
+    // Split the mesh into its triangles, to be added to a `HittableList` and put in a `BvhNode`.
+    pub fn triangles(self) -> Vec<HittableObject> {
+        let mesh = Arc::new(self);
+        (0..mesh.indices.len())
+            .map(|index| {
+                HittableObject::Triangle(Triangle {
+                    mesh: Arc::clone(&mesh),
+                    index,
+                })
+            })
+            .collect()
+    }
+}
+
+// Triangles covering a solid angle outside this range are sampled by area instead, since spherical
+// sampling loses precision when they're tiny and isn't worth it when they're huge.
+const MIN_SPHERICAL_SAMPLE_AREA: f32 = 3e-4;
+const MAX_SPHERICAL_SAMPLE_AREA: f32 = 6.22;
+
+// A single triangle referencing its mesh.
+#[derive(Clone)]
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+}
+
+impl Triangle {
+    fn vertices(&self) -> [usize; 3] {
+        self.mesh.indices[self.index]
+    }
+
+    fn positions(&self) -> [Vec3; 3] {
+        self.vertices().map(|i| self.mesh.positions[i])
+    }
+
+    fn uvs(&self) -> [(f32, f32); 3] {
+        if self.mesh.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]
+        } else {
+            self.vertices().map(|i| self.mesh.uvs[i])
+        }
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.mesh.mat
+    }
+
+    // The mesh's address and the triangle's place in it, the same for every clone.
+    pub(crate) fn id(&self) -> (usize, usize) {
+        (Arc::as_ptr(&self.mesh) as usize, self.index)
+    }
+
+    pub fn area(&self) -> f32 {
+        let [p0, p1, p2] = self.positions();
+        0.5 * cross(&(p1 - p0), &(p2 - p0)).length()
+    }
+
+    // Solid angle of the triangle seen from `origin`, from "The Solid Angle of a Plane Triangle"
+    // (Van Oosterom and Strackee 1983).
+    fn solid_angle(&self, origin: &Vec3) -> f32 {
+        let [a, b, c] = self.positions().map(|p| (p - *origin).normalize());
+        let numerator = dot(&a, &cross(&b, &c)).abs();
+        let denominator = 1.0 + dot(&a, &b) + dot(&b, &c) + dot(&c, &a);
+        2.0 * numerator.atan2(denominator).abs()
+    }
+
+    // Uniformly samples a direction within the triangle's solid angle, from "Stratified Sampling of
+    // Spherical Triangles" (Arvo 1995).
+    fn sample_spherical(&self, origin: &Vec3) -> Option<Vec3> {
+        let [a, b, c] = self.positions().map(|p| (p - *origin).normalize());
+        let n_ab = cross(&a, &b);
+        let n_bc = cross(&b, &c);
+        let n_ca = cross(&c, &a);
+        if [n_ab, n_bc, n_ca].iter().any(|n| n.length_squared() == 0.0) {
+            return None;
+        }
+        let (n_ab, n_bc, n_ca) = (n_ab.normalize(), n_bc.normalize(), n_ca.normalize());
+
+        // Interior angles at the vertices and the area of the spherical triangle.
+        let alpha = angle_between(&n_ab, &-n_ca);
+        let beta = angle_between(&n_bc, &-n_ab);
+        let gamma = angle_between(&n_ca, &-n_bc);
+        let a_pi = alpha + beta + gamma;
+
+        // Pick the sub-triangle with a fraction of the area, which places the new vertex on the
+        // edge from a to c.
+        let ap_pi = PI + rand_f32() * (a_pi - PI);
+        let (sin_alpha, cos_alpha) = alpha.sin_cos();
+        let sin_phi = ap_pi.sin() * cos_alpha - ap_pi.cos() * sin_alpha;
+        let cos_phi = ap_pi.cos() * cos_alpha + ap_pi.sin() * sin_alpha;
+        let k1 = cos_phi + cos_alpha;
+        let k2 = sin_phi - sin_alpha * dot(&a, &b);
+        let cos_bp = ((k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha)
+            / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha))
+            .clamp(-1.0, 1.0);
+        let sin_bp = (1.0 - cos_bp * cos_bp).max(0.0).sqrt();
+        let cp = cos_bp * a + sin_bp * gram_schmidt(&c, &a);
+
+        // Then pick a point on the arc from b to the new vertex.
+        let cos_theta = 1.0 - rand_f32() * (1.0 - dot(&cp, &b));
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        Some(cos_theta * b + sin_theta * gram_schmidt(&cp, &b))
+    }
+
+    fn use_spherical_sampling(solid_angle: f32) -> bool {
+        (MIN_SPHERICAL_SAMPLE_AREA..=MAX_SPHERICAL_SAMPLE_AREA).contains(&solid_angle)
+    }
+}
+
+// Angle between two unit vectors, accurate when they're nearly parallel.
+fn angle_between(v1: &Vec3, v2: &Vec3) -> f32 {
+    if dot(v1, v2) < 0.0 {
+        PI - 2.0 * ((*v1 + *v2).length() / 2.0).min(1.0).asin()
+    } else {
+        2.0 * ((*v2 - *v1).length() / 2.0).min(1.0).asin()
+    }
+}
+
+// The part of unit vector `v` perpendicular to unit vector `w`, normalized.
+fn gram_schmidt(v: &Vec3, w: &Vec3) -> Vec3 {
+    let perpendicular = *v - dot(v, w) * *w;
+    if perpendicular.length_squared() == 0.0 {
+        return Vec3::default();
+    }
+    perpendicular.normalize()
+}
+
+impl Hittable for Triangle {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        // Moller-Trumbore intersection.
+        let [p0, p1, p2] = self.positions();
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let pvec = cross(r.direction(), &e2);
+        let det = dot(&e1, &pvec);
+        if det.abs() < 1e-12 {
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = *r.origin() - p0;
+        let b1 = dot(&tvec, &pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+        let qvec = cross(&tvec, &e1);
+        let b2 = dot(r.direction(), &qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+        let t = dot(&e2, &qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+        let b0 = 1.0 - b1 - b2;
+
+        // Interpolate the texture coordinates and find the derivatives of the position along them.
+        let [uv0, uv1, uv2] = self.uvs();
+        rec.u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        rec.v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+        let uv_det = du02 * dv12 - dv02 * du12;
+        let (dpdu, dpdv) = if uv_det.abs() < 1e-9 {
+            (e1, e2)
+        } else {
+            let inv = 1.0 / uv_det;
+            (
+                (dv12 * dp02 - dv02 * dp12) * inv,
+                (du02 * dp12 - du12 * dp02) * inv,
+            )
+        };
+
+        // Vertex normals decide which side is the outside when they disagree with the winding.
+        let mut outward_normal = cross(&e1, &e2).normalize();
+        let shading_normal = if self.mesh.normals.is_empty() {
+            outward_normal
+        } else {
+            let [n0, n1, n2] = self.vertices().map(|i| self.mesh.normals[i]);
+            let n = (b0 * n0 + b1 * n1 + b2 * n2).normalize();
+            if dot(&n, &outward_normal) < 0.0 {
+                outward_normal = -outward_normal;
+            }
+            n
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.mat = &self.mesh.mat;
+        rec.set_face_normal(r, outward_normal);
+        rec.set_shading(dpdu, dpdv, shading_normal);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [p0, p1, p2] = self.positions();
+        Aabb::surrounding(&Aabb::from_points(p0, p1), &Aabb::from_points(p0, p2))
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(*origin, *direction),
+            &Interval::new(0.001, f32::INFINITY),
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        let solid_angle = self.solid_angle(origin);
+        if Self::use_spherical_sampling(solid_angle) {
+            return 1.0 / solid_angle;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (dot(direction, &rec.geometric_normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        // Sample the solid angle directly when the triangle covers a reasonable part of the
+        // sphere, so nearby panels don't waste samples on their grazing edges.
+        if Self::use_spherical_sampling(self.solid_angle(origin)) {
+            if let Some(direction) = self.sample_spherical(origin) {
+                return direction;
+            }
+        }
+
+        // Otherwise uniformly sample a point on the triangle.
+        let [p0, p1, p2] = self.positions();
+        let su0 = rand_f32().sqrt();
+        let (b0, b1) = (1.0 - su0, rand_f32() * su0);
+        let p = b0 * p0 + b1 * p1 + (1.0 - b0 - b1) * p2;
+        p - *origin
+    }
+}
+
+#[test]
+fn test_triangle_hit() {
+    let mesh = TriangleMesh::new(
+        vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ],
+        vec![[0, 1, 2]],
+        Material::default(),
+    )
+    .with_uvs(vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)])
+    .with_normals(vec![Vec3::new(0.0, 0.0, 1.0); 3]);
+    let triangles = mesh.triangles();
+    let mut rec = HitRecord::default();
+    let interval = Interval::new(0.001, f32::INFINITY);
+
+    let r = Ray::new(Vec3::new(0.25, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    assert!(triangles[0].hit(&r, &interval, &mut rec));
+    assert!((rec.t - 1.0).abs() < 1e-5);
+    assert!((rec.u - 0.25).abs() < 1e-5 && (rec.v - 0.5).abs() < 1e-5);
+    assert!(!rec.front_face);
+    assert!((rec.dpdu - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-5);
+    assert!((rec.dpdv - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-5);
+
+    let miss = Ray::new(Vec3::new(0.75, 0.75, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    assert!(!triangles[0].hit(&miss, &interval, &mut rec));
+}
+
+#[test]
+fn test_spherical_triangle_sampling() {
+    let positions = vec![
+        Vec3::new(-1.0, 1.0, -1.0),
+        Vec3::new(1.0, 1.0, -1.0),
+        Vec3::new(0.0, 1.0, 1.0),
+    ];
+    let triangles = TriangleMesh::new(positions, vec![[0, 1, 2]], Material::default()).triangles();
+    let HittableObject::Triangle(triangle) = &triangles[0] else {
+        unreachable!();
+    };
+
+    // Close up the triangle is sampled by solid angle, and every sample lands on it.
+    let origin = Vec3::new(0.2, 0.0, 0.0);
+    let solid_angle = triangle.solid_angle(&origin);
+    assert!(Triangle::use_spherical_sampling(solid_angle));
+    for _ in 0..200 {
+        let direction = triangle.random(&origin);
+        assert!((triangle.pdf_value(&origin, &direction) - 1.0 / solid_angle).abs() < 1e-4);
+    }
+
+    // Far away, the solid angle matches the projected area over the squared distance.
+    let far = Vec3::new(0.0, 101.0, 0.0);
+    assert!((triangle.solid_angle(&far) - triangle.area() / 1e4).abs() < 1e-6);
+}
@@ -0,0 +1,190 @@
+use std::f32::consts::PI;
+
+use crate::vec3::{cross, dot, Vec3};
+
+// Trigonometric helpers for directions expressed in a local shading frame where +z is the normal.
+pub fn cos_theta(w: &Vec3) -> f32 {
+    w.z()
+}
+
+pub fn cos2_theta(w: &Vec3) -> f32 {
+    w.z() * w.z()
+}
+
+pub fn sin2_theta(w: &Vec3) -> f32 {
+    (1.0 - cos2_theta(w)).max(0.0)
+}
+
+pub fn tan2_theta(w: &Vec3) -> f32 {
+    sin2_theta(w) / cos2_theta(w)
+}
+
+pub fn cos_phi(w: &Vec3) -> f32 {
+    let sin_theta = sin2_theta(w).sqrt();
+    if sin_theta == 0.0 {
+        1.0
+    } else {
+        (w.x() / sin_theta).clamp(-1.0, 1.0)
+    }
+}
+
+pub fn sin_phi(w: &Vec3) -> f32 {
+    let sin_theta = sin2_theta(w).sqrt();
+    if sin_theta == 0.0 {
+        0.0
+    } else {
+        (w.y() / sin_theta).clamp(-1.0, 1.0)
+    }
+}
+
+// Reflects `wo` about the microfacet normal `wm`; both point away from the surface.
+pub fn reflect_local(wo: &Vec3, wm: &Vec3) -> Vec3 {
+    -*wo + 2.0 * dot(wo, wm) * *wm
+}
+
+// Refracts `wi` through the interface with normal `n` on the same side as `wi`, where `eta` is the
+// relative index of refraction across it. Returns None on total internal reflection.
+pub fn refract_local(wi: &Vec3, n: &Vec3, eta: f32) -> Option<Vec3> {
+    let cos_theta_i = dot(n, wi);
+    let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = f32::sqrt(1.0 - sin2_theta_t);
+    Some(-*wi / eta + (cos_theta_i / eta - cos_theta_t) * *n)
+}
+
+// Trowbridge-Reitz (GGX) microfacet distribution with anisotropic roughness.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> Self {
+        let mut distrib = TrowbridgeReitz { alpha_x, alpha_y };
+        if !distrib.effectively_smooth() {
+            // Very small alpha values cause numerical trouble, so clamp them once the surface
+            // is known to be rough.
+            distrib.alpha_x = distrib.alpha_x.max(1e-4);
+            distrib.alpha_y = distrib.alpha_y.max(1e-4);
+        }
+        distrib
+    }
+
+    // Map a perceptual roughness in [0, 1] and an anisotropy in [0, 1) to alpha values, stretching
+    // the highlight along the tangent as anisotropy increases.
+    pub fn from_roughness(roughness: f32, anisotropy: f32) -> Self {
+        let alpha = roughness * roughness;
+        let aspect = f32::sqrt(1.0 - 0.9 * anisotropy.clamp(0.0, 1.0));
+        Self::new(alpha / aspect, alpha * aspect)
+    }
+
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    // Microfacet normal distribution.
+    pub fn d(&self, wm: &Vec3) -> f32 {
+        let tan2_theta = tan2_theta(wm);
+        if tan2_theta.is_infinite() {
+            return 0.0;
+        }
+        let cos4_theta = cos2_theta(wm) * cos2_theta(wm);
+        if cos4_theta < 1e-16 {
+            return 0.0;
+        }
+        let e = tan2_theta
+            * ((cos_phi(wm) / self.alpha_x).powi(2) + (sin_phi(wm) / self.alpha_y).powi(2));
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos4_theta * (1.0 + e) * (1.0 + e))
+    }
+
+    // Smith's auxiliary function for the height-correlated masking-shadowing term.
+    pub fn lambda(&self, w: &Vec3) -> f32 {
+        let tan2_theta = tan2_theta(w);
+        if tan2_theta.is_infinite() {
+            return 0.0;
+        }
+        let alpha2 = (cos_phi(w) * self.alpha_x).powi(2) + (sin_phi(w) * self.alpha_y).powi(2);
+        (f32::sqrt(1.0 + alpha2 * tan2_theta) - 1.0) / 2.0
+    }
+
+    // Masking of microfacets seen from a single direction.
+    pub fn g1(&self, w: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Masking-shadowing for a pair of directions.
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Distribution of normals visible from `w`.
+    pub fn d_visible(&self, w: &Vec3, wm: &Vec3) -> f32 {
+        self.g1(w) / cos_theta(w).abs() * self.d(wm) * dot(w, wm).abs()
+    }
+
+    pub fn pdf(&self, w: &Vec3, wm: &Vec3) -> f32 {
+        self.d_visible(w, wm)
+    }
+
+    // Samples a visible microfacet normal from the direction `w`, following Heitz's
+    // "Sampling the GGX Distribution of Visible Normals".
+    pub fn sample_wm(&self, w: &Vec3, u: (f32, f32)) -> Vec3 {
+        // Transform w to the hemispherical configuration.
+        let mut wh = Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()).normalize();
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+
+        // Find an orthonormal basis for the visible normal sampling.
+        let t1 = if wh.z() < 0.99999 {
+            cross(&Vec3::new(0.0, 0.0, 1.0), &wh).normalize()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = cross(&wh, &t1);
+
+        // Generate a uniformly distributed point on the unit disk and warp it to the hemisphere.
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let (px, mut py) = (r * phi.cos(), r * phi.sin());
+        let h = f32::sqrt(1.0 - px * px);
+        let s = (1.0 + wh.z()) / 2.0;
+        py = (1.0 - s) * h + s * py;
+
+        // Project the point onto the hemisphere and transform back to the ellipsoid.
+        let pz = f32::sqrt((1.0 - px * px - py * py).max(0.0));
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        )
+        .normalize()
+    }
+}
+
+#[test]
+fn test_visible_normals_normalized() {
+    use crate::vec3::random_vec;
+
+    // Integrating the visible normal distribution over the sphere should give one.
+    let distrib = TrowbridgeReitz::new(0.5, 0.8);
+    let w = Vec3::new(0.3, -0.2, 0.9).normalize();
+    let n = 200_000;
+    let sum: f32 = (0..n)
+        .map(|_| {
+            let wm = random_vec();
+            if wm.z() <= 0.0 {
+                0.0
+            } else {
+                distrib.d_visible(&w, &wm) * 4.0 * PI
+            }
+        })
+        .sum();
+
+    assert!((sum / n as f32 - 1.0).abs() < 0.03);
+}
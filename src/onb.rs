@@ -0,0 +1,61 @@
+use crate::vec3::{cross, dot, Vec3};
+
+// Orthonormal basis built around a normal, with `w` along the normal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    pub fn new(n: &Vec3) -> Self {
+        let w = n.normalize();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = cross(&w, &a).normalize();
+        let u = cross(&w, &v);
+
+        Onb { axis: [u, v, w] }
+    }
+
+    // Basis around `n` with `u` along `tangent`, which only needs to be roughly perpendicular.
+    pub fn from_normal_tangent(n: &Vec3, tangent: &Vec3) -> Self {
+        let w = n.normalize();
+        let t = *tangent - w * dot(&w, tangent);
+        if t.length_squared() < 1e-12 {
+            return Self::new(n);
+        }
+        let u = t.normalize();
+        let v = cross(&w, &u);
+
+        Onb { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> &Vec3 {
+        &self.axis[0]
+    }
+
+    pub fn v(&self) -> &Vec3 {
+        &self.axis[1]
+    }
+
+    pub fn w(&self) -> &Vec3 {
+        &self.axis[2]
+    }
+
+    // Transform from basis coordinates to local space.
+    pub fn transform(&self, v: &Vec3) -> Vec3 {
+        (v.x() * self.axis[0]) + (v.y() * self.axis[1]) + (v.z() * self.axis[2])
+    }
+
+    // Transform from local space to basis coordinates.
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            dot(v, &self.axis[0]),
+            dot(v, &self.axis[1]),
+            dot(v, &self.axis[2]),
+        )
+    }
+}
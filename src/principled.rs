@@ -0,0 +1,394 @@
+use std::f32::consts::PI;
+
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Lobe},
+    color::luminance,
+    dielectric::RoughDielectric,
+    hittable::HitRecord,
+    microfacet::{cos_theta, reflect_local, TrowbridgeReitz},
+    ray::Ray,
+    util::rand_f32,
+    vec3::{dot, random_cosine_direction, Vec3},
+};
+
+// Keep the specular lobes glossy so every lobe can be evaluated and mixed by its pdf.
+const MIN_ALPHA: f32 = 2e-3;
+
+// Disney style "principled" uber-material. All parameters except the colours are in [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Principled {
+    pub base_color: Vec3,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub specular: f32, // Dielectric reflectance, where 0.5 is 4% at normal incidence
+    pub specular_tint: f32,
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_gloss: f32,
+    pub transmission: f32,
+    pub ior: f32, // Index of refraction of the transmissive part
+    pub emission: Vec3,
+}
+
+impl Principled {
+    // Build the material from glTF metallic-roughness parameters, including the
+    // KHR_materials_ior and KHR_materials_transmission extensions.
+    pub fn from_gltf(
+        base_color: Vec3,
+        metallic: f32,
+        roughness: f32,
+        emissive: Vec3,
+        ior: f32,
+        transmission: f32,
+    ) -> Self {
+        let f0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+        Principled {
+            base_color,
+            metallic,
+            roughness,
+            specular: (f0 / 0.08).clamp(0.0, 1.0),
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.0,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission,
+            ior,
+            emission: emissive,
+        }
+    }
+
+    fn tint(&self) -> Vec3 {
+        let lum = luminance(&self.base_color);
+        if lum > 0.0 {
+            self.base_color / lum
+        } else {
+            Vec3::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    fn specular_distrib(&self) -> TrowbridgeReitz {
+        let alpha = (self.roughness * self.roughness).max(MIN_ALPHA);
+        TrowbridgeReitz::new(alpha, alpha)
+    }
+
+    fn dielectric(&self) -> RoughDielectric {
+        RoughDielectric {
+            refraction_index: self.ior.into(),
+            distrib: self.specular_distrib(),
+            absorption: Vec3::default(),
+            thin_film: None,
+        }
+    }
+
+    // Rays inside a transmissive object only see the dielectric interface. The base colour tints
+    // light once, as it refracts in.
+    fn inside(&self, rec: &HitRecord) -> bool {
+        !rec.front_face && self.transmission > 0.0 && self.metallic < 1.0
+    }
+
+    fn clearcoat_alpha(&self) -> f32 {
+        lerp(0.1, 0.001, self.clearcoat_gloss)
+    }
+
+    // Probabilities of sampling the diffuse, specular, clearcoat and transmission lobes.
+    fn lobe_probabilities(&self) -> [f32; 4] {
+        let weights = [
+            (1.0 - self.metallic) * (1.0 - self.transmission),
+            1.0,
+            0.25 * self.clearcoat,
+            (1.0 - self.metallic) * self.transmission,
+        ];
+        let total: f32 = weights.iter().sum();
+        weights.map(|w| w / total)
+    }
+
+    // Sum of the reflection lobes for local directions on the same side as the normal.
+    fn f_reflection(&self, wo: &Vec3, wi: &Vec3) -> Vec3 {
+        let cos_theta_o = cos_theta(wo);
+        let cos_theta_i = cos_theta(wi);
+        let wm = (*wo + *wi).normalize();
+        let cos_theta_d = dot(wi, &wm);
+        let white = Vec3::new(1.0, 1.0, 1.0);
+
+        // Diffuse with retro-reflection and sheen.
+        let diffuse_weight = (1.0 - self.metallic) * (1.0 - self.transmission);
+        let fl = schlick_weight(cos_theta_i);
+        let fv = schlick_weight(cos_theta_o);
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_theta_d * cos_theta_d;
+        let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let sheen_color = lerp_vec(white, self.tint(), self.sheen_tint);
+        let sheen = self.sheen * schlick_weight(cos_theta_d) * sheen_color;
+        let diffuse = diffuse_weight * (self.base_color * (fd / PI) + sheen);
+
+        // Specular, tinted towards the base colour for metals.
+        let spec_tint = lerp_vec(white, self.tint(), self.specular_tint);
+        let spec_color = lerp_vec(
+            self.specular * 0.08 * spec_tint,
+            self.base_color,
+            self.metallic,
+        );
+        let fs = lerp_vec(spec_color, white, schlick_weight(cos_theta_d));
+        let distrib = self.specular_distrib();
+        let specular =
+            (distrib.d(&wm) * distrib.g(wo, wi) / (4.0 * cos_theta_i * cos_theta_o)) * fs;
+
+        // Clearcoat with a fixed index of refraction of 1.5.
+        let dr = gtr1(cos_theta(&wm), self.clearcoat_alpha());
+        let fr = lerp(0.04, 1.0, schlick_weight(cos_theta_d));
+        let gr = smith_g_ggx(cos_theta_i, 0.25) * smith_g_ggx(cos_theta_o, 0.25);
+        let clearcoat = 0.25 * self.clearcoat * dr * fr * gr;
+
+        diffuse + specular + Vec3::new(clearcoat, clearcoat, clearcoat)
+    }
+
+    fn clearcoat_pdf(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        let wm = (*wo + *wi).normalize();
+        gtr1(cos_theta(&wm), self.clearcoat_alpha()) * cos_theta(&wm) / (4.0 * dot(wo, &wm))
+    }
+
+    fn local_eval(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        wo: &Vec3,
+        wi: &Vec3,
+        direction: &Vec3,
+    ) -> Vec3 {
+        let transmission_weight = (1.0 - self.metallic) * self.transmission;
+        // The specular lobe already reflects off the dielectric, so only its refracted part is
+        // added, and only the refracted light picks up the base colour.
+        let transmission = if transmission_weight > 0.0 {
+            transmission_weight
+                * r_in.spectrum(&self.base_color)
+                * self.dielectric().eval_refraction(r_in, rec, direction)
+        } else {
+            Vec3::default()
+        };
+
+        if cos_theta(wo) <= 0.0 || cos_theta(wi) <= 0.0 {
+            return transmission;
+        }
+        r_in.spectrum(&self.f_reflection(wo, wi)) * cos_theta(wi) + transmission
+    }
+
+    fn local_pdf(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        wo: &Vec3,
+        wi: &Vec3,
+        direction: &Vec3,
+    ) -> f32 {
+        let [p_diffuse, p_specular, p_clearcoat, p_transmission] = self.lobe_probabilities();
+        let transmission = if p_transmission > 0.0 {
+            p_transmission * self.dielectric().refraction_pdf(r_in, rec, direction)
+        } else {
+            0.0
+        };
+
+        if cos_theta(wo) <= 0.0 || cos_theta(wi) <= 0.0 {
+            return transmission;
+        }
+        let wm = (*wo + *wi).normalize();
+        let specular = self.specular_distrib().pdf(wo, &wm) / (4.0 * dot(wo, &wm));
+
+        p_diffuse * cos_theta(wi) / PI
+            + p_specular * specular
+            + p_clearcoat * self.clearcoat_pdf(wo, wi)
+            + transmission
+    }
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            base_color: Vec3::new(0.8, 0.8, 0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ior: 1.5,
+            emission: Vec3::default(),
+        }
+    }
+}
+
+impl Bsdf for Principled {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        if self.inside(rec) {
+            return self.dielectric().sample(r_in, rec);
+        }
+
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        if cos_theta(&wo) <= 0.0 {
+            return None;
+        }
+
+        // Pick a lobe and sample a direction from it.
+        let [p_diffuse, p_specular, p_clearcoat, _] = self.lobe_probabilities();
+        let u = rand_f32();
+        let (wi, lobe) = if u < p_diffuse {
+            (random_cosine_direction(), Lobe::DIFFUSE | Lobe::REFLECTION)
+        } else if u < p_diffuse + p_specular {
+            let wm = self
+                .specular_distrib()
+                .sample_wm(&wo, (rand_f32(), rand_f32()));
+            (reflect_local(&wo, &wm), Lobe::GLOSSY | Lobe::REFLECTION)
+        } else if u < p_diffuse + p_specular + p_clearcoat {
+            let wm = sample_gtr1(self.clearcoat_alpha());
+            (reflect_local(&wo, &wm), Lobe::GLOSSY | Lobe::REFLECTION)
+        } else {
+            let direction = self.dielectric().sample_refraction(r_in, rec)?;
+            (uvw.to_local(&direction), Lobe::GLOSSY | Lobe::TRANSMISSION)
+        };
+
+        let direction = uvw.transform(&wi);
+        let pdf = self.local_pdf(r_in, rec, &wo, &wi, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let f = self.local_eval(r_in, rec, &wo, &wi, &direction);
+
+        Some(BsdfSample {
+            direction,
+            weight: f / pdf,
+            pdf,
+            lobe: if cos_theta(&wi) < 0.0 {
+                Lobe::GLOSSY | Lobe::TRANSMISSION
+            } else {
+                lobe
+            },
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        if self.inside(rec) {
+            return self.dielectric().eval(r_in, rec, direction);
+        }
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        self.local_eval(r_in, rec, &wo, &wi, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        if self.inside(rec) {
+            return self.dielectric().pdf(r_in, rec, direction);
+        }
+        let uvw = rec.frame();
+        let wo = uvw.to_local(&-r_in.direction().normalize());
+        let wi = uvw.to_local(&direction.normalize());
+        self.local_pdf(r_in, rec, &wo, &wi, direction)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        if rec.front_face {
+            r_in.spectrum(&self.emission)
+        } else {
+            Vec3::default()
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    (1.0 - t) * a + t * b
+}
+
+fn lerp_vec(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
+
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+// Generalized Trowbridge-Reitz distribution with gamma = 1, used by the clearcoat.
+fn gtr1(cos_theta_m: f32, alpha: f32) -> f32 {
+    if alpha >= 1.0 {
+        return 1.0 / PI;
+    }
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * cos_theta_m * cos_theta_m;
+    (a2 - 1.0) / (PI * a2.ln() * t)
+}
+
+fn sample_gtr1(alpha: f32) -> Vec3 {
+    let a2 = alpha * alpha;
+    let cos_theta = f32::sqrt((1.0 - a2.powf(1.0 - rand_f32())) / (1.0 - a2)).clamp(0.0, 1.0);
+    let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
+    let phi = 2.0 * PI * rand_f32();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// Separable Smith masking term with the 1 / (2 cos_theta) factor folded in.
+fn smith_g_ggx(cos_theta: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let c2 = cos_theta * cos_theta;
+    1.0 / (cos_theta + f32::sqrt(a2 + c2 - a2 * c2))
+}
+
+#[test]
+fn test_sample_matches_eval() {
+    let mat = Principled {
+        base_color: Vec3::new(0.9, 0.4, 0.2),
+        metallic: 0.3,
+        roughness: 0.4,
+        sheen: 0.5,
+        clearcoat: 0.7,
+        clearcoat_gloss: 0.5,
+        transmission: 0.4,
+        ..Default::default()
+    };
+    let rec = HitRecord {
+        normal: Vec3::new(0.0, 1.0, 0.0),
+        ..Default::default()
+    };
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.3, -1.0, -0.6));
+
+    for front_face in [true, false] {
+        let rec = HitRecord { front_face, ..rec };
+
+        for _ in 0..500 {
+            let Some(srec) = mat.sample(&r_in, &rec) else {
+                continue;
+            };
+            let pdf = mat.pdf(&r_in, &rec, &srec.direction);
+            let expected = mat.eval(&r_in, &rec, &srec.direction) / pdf;
+
+            assert!((pdf - srec.pdf).abs() <= 1e-2 * pdf);
+            assert!((expected - srec.weight).length() <= 1e-2 * expected.length());
+        }
+    }
+}
+
+#[test]
+fn test_transmission_reflects_once() {
+    // With a black base only the specular lobe reflects, whether or not the rest transmits.
+    let opaque = Principled {
+        base_color: Vec3::default(),
+        roughness: 0.3,
+        ..Default::default()
+    };
+    let glass = Principled {
+        transmission: 1.0,
+        ..opaque
+    };
+    let rec = HitRecord {
+        normal: Vec3::new(0.0, 1.0, 0.0),
+        front_face: true,
+        ..Default::default()
+    };
+    let r_in = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -0.8));
+    for direction in [Vec3::new(0.0, 1.0, -0.8), Vec3::new(0.3, 1.0, -0.5)] {
+        let reflected = glass.eval(&r_in, &rec, &direction);
+        assert!((reflected - opaque.eval(&r_in, &rec, &direction)).length() < 1e-6);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    util::rand_f32,
+    vec3::{cross, dot, Vec3},
+};
+
+// Parallelogram with corner `q` and edges `u` and `v`. The surface coordinates run from 0 to 1
+// along each edge.
+#[derive(Clone)]
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub mat: Material,
+    normal: Vec3,
+    d: f32,  // Plane equation constant, dot(normal, p) = d
+    w: Vec3, // Constant for computing the planar coordinates of a point
+    area: f32,
+    bbox: Aabb,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, mat: Material) -> Self {
+        let n = cross(&u, &v);
+        let normal = n.normalize();
+
+        // Compute the bounding box of all four vertices.
+        let bbox = Aabb::surrounding(
+            &Aabb::from_points(q, q + u + v),
+            &Aabb::from_points(q + u, q + v),
+        );
+
+        Quad {
+            q,
+            u,
+            v,
+            mat,
+            normal,
+            d: dot(&normal, &q),
+            w: n / dot(&n, &n),
+            area: n.length(),
+            bbox,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.area
+    }
+}
+
+impl Hittable for Quad {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        let denom = dot(&self.normal, r.direction());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        // Return false if the hit point parameter t is outside the ray interval.
+        let t = (self.d - dot(&self.normal, r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates.
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = dot(&self.w, &cross(&planar_hitpt_vector, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar_hitpt_vector));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = &self.mat;
+        rec.set_face_normal(r, self.normal);
+        rec.set_shading(self.u, self.v, self.normal);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(*origin, *direction),
+            &Interval::new(0.001, f32::INFINITY),
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (dot(direction, &rec.geometric_normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let p = self.q + (rand_f32() * self.u) + (rand_f32() * self.v);
+        p - *origin
+    }
+}
+
+#[test]
+fn test_quad_hit() {
+    let quad = Quad::new(
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 2.0, 0.0),
+        Material::default(),
+    );
+    let mut rec = HitRecord::default();
+    let interval = Interval::new(0.001, f32::INFINITY);
+
+    let r = Ray::new(Vec3::new(0.5, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(quad.hit(&r, &interval, &mut rec));
+    assert!((rec.t - 2.0).abs() < 1e-5);
+    assert!((rec.u - 0.75).abs() < 1e-5 && (rec.v - 0.5).abs() < 1e-5);
+    assert!(rec.front_face);
+    assert!((rec.tangent - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-5);
+    assert!((rec.bitangent - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-5);
+
+    let miss = Ray::new(Vec3::new(1.5, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(!quad.hit(&miss, &interval, &mut rec));
+}
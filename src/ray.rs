@@ -1,16 +1,29 @@
 use std::fmt::Display;
 
-use crate::Vec3;
+use crate::{
+    spectrum::{Wavelengths, RGB_WAVELENGTHS},
+    Vec3,
+};
 
 #[derive(Clone, Copy, PartialEq, PartialOrd, Default, Debug)]
 pub struct Ray {
     origin: Vec3,
     direction: Vec3,
+    wavelengths: Option<Wavelengths>, // Set in spectral mode, where colours are per wavelength
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            wavelengths: None,
+        }
+    }
+
+    pub fn with_wavelengths(mut self, wavelengths: Option<Wavelengths>) -> Self {
+        self.wavelengths = wavelengths;
+        self
     }
 
     pub fn at(&self, t: f32) -> Vec3 {
@@ -24,6 +37,36 @@ impl Ray {
     pub fn direction(&self) -> &Vec3 {
         &self.direction
     }
+
+    pub fn wavelengths(&self) -> Option<Wavelengths> {
+        self.wavelengths
+    }
+
+    // Wavelength each colour channel stands for, in nanometers.
+    pub fn channel_wavelengths(&self) -> [f32; 3] {
+        match &self.wavelengths {
+            Some(wavelengths) => [0, 1, 2].map(|i| wavelengths.lambda(i)),
+            None => RGB_WAVELENGTHS,
+        }
+    }
+
+    // An RGB reflectance or emission as carried by this ray: unchanged for RGB rays, or its
+    // spectrum at the ray's wavelengths in spectral mode.
+    pub fn spectrum(&self, rgb: &Vec3) -> Vec3 {
+        match &self.wavelengths {
+            Some(wavelengths) => wavelengths.upsample(rgb),
+            None => *rgb,
+        }
+    }
+
+    // An RGB parameter that isn't a colour, like an index of refraction, interpolated at the
+    // ray's wavelengths in spectral mode.
+    pub fn interpolate(&self, rgb: &Vec3) -> Vec3 {
+        match &self.wavelengths {
+            Some(wavelengths) => wavelengths.interpolate(rgb),
+            None => *rgb,
+        }
+    }
 }
 
 impl Display for Ray {
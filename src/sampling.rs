@@ -0,0 +1,248 @@
+// Piecewise-constant distribution over [0, 1) for importance sampling tabulated functions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    func_int: f32, // Integral of the function over [0, 1)
+}
+
+impl Distribution1D {
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(func: &[f32]) -> Self {
+        assert!(!func.is_empty());
+        let n = func.len();
+        let func: Vec<f32> = func.iter().map(|f| f.abs()).collect();
+
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1] / n as f32;
+        }
+        let func_int = cdf[n];
+        if func_int == 0.0 {
+            // Fall back to uniform sampling for an all zero function.
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f32 / n as f32;
+            }
+        } else {
+            for c in &mut cdf {
+                *c /= func_int;
+            }
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            func_int,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.func_int
+    }
+
+    // Maps `u` in [0, 1) to a sample in [0, 1), returning it with its density and the index of the
+    // segment it fell in.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        // Find the last cdf entry not above u.
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+
+        let x = ((offset as f32 + du) / self.count() as f32).min(1.0 - f32::EPSILON);
+        (x, self.pdf_index(offset), offset)
+    }
+
+    // Density of sampling a point in segment `index`.
+    pub fn pdf_index(&self, index: usize) -> f32 {
+        if self.func_int == 0.0 {
+            return 1.0;
+        }
+        self.func[index] / self.func_int
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn pdf(&self, x: f32) -> f32 {
+        let index = ((x * self.count() as f32) as usize).min(self.count() - 1);
+        self.pdf_index(index)
+    }
+}
+
+// Piecewise-constant distribution over [0, 1)², such as an image, sampled by first picking a row
+// from the marginal and then a column within it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // `func` holds `width` values per row, row by row.
+    pub fn new(func: &[f32], width: usize) -> Self {
+        assert!(width > 0 && func.len().is_multiple_of(width));
+        let conditional: Vec<Distribution1D> =
+            func.chunks_exact(width).map(Distribution1D::new).collect();
+        let marginal: Vec<f32> = conditional.iter().map(Distribution1D::integral).collect();
+
+        Distribution2D {
+            conditional,
+            marginal: Distribution1D::new(&marginal),
+        }
+    }
+
+    // Returns a point as (column, row) coordinates in [0, 1) with its density.
+    pub fn sample(&self, u: (f32, f32)) -> ((f32, f32), f32) {
+        let (y, pdf_y, row) = self.marginal.sample(u.1);
+        let (x, pdf_x, _) = self.conditional[row].sample(u.0);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        let rows = self.conditional.len();
+        let row = ((y * rows as f32) as usize).min(rows - 1);
+        self.marginal.pdf_index(row) * self.conditional[row].pdf(x)
+    }
+}
+
+// Picks one of a set of items in proportion to their weights in constant time, using Vose's
+// alias method.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AliasTable {
+    pmf: Vec<f32>,
+    threshold: Vec<f32>, // Chance of keeping a bin's own item rather than its alias
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(weights: &[f32]) -> Self {
+        let n = weights.len();
+        let total: f32 = weights.iter().map(|w| w.max(0.0)).sum();
+        let pmf: Vec<f32> = if total > 0.0 {
+            weights.iter().map(|w| w.max(0.0) / total).collect()
+        } else {
+            vec![1.0 / n as f32; n]
+        };
+
+        // Fill bins one average share at a time, topping up light items from heavy ones.
+        let mut threshold: Vec<f32> = pmf.iter().map(|p| p * n as f32).collect();
+        let mut alias: Vec<usize> = (0..n).collect();
+        let (mut under, mut over): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| threshold[i] < 1.0);
+        while let (Some(&light), Some(&heavy)) = (under.last(), over.last()) {
+            under.pop();
+            alias[light] = heavy;
+            threshold[heavy] -= 1.0 - threshold[light];
+            if threshold[heavy] < 1.0 {
+                over.pop();
+                under.push(heavy);
+            }
+        }
+        // Whatever is left over is only off by rounding.
+        for i in under.into_iter().chain(over) {
+            threshold[i] = 1.0;
+        }
+
+        AliasTable {
+            pmf,
+            threshold,
+            alias,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pmf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pmf.is_empty()
+    }
+
+    // Maps `u` in [0, 1) to an item, returning it with its probability.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample(&self, u: f32) -> Option<(usize, f32)> {
+        if self.is_empty() {
+            return None;
+        }
+        let scaled = u * self.len() as f32;
+        let bin = (scaled as usize).min(self.len() - 1);
+        let index = if scaled - (bin as f32) < self.threshold[bin] {
+            bin
+        } else {
+            self.alias[bin]
+        };
+        Some((index, self.pmf[index]))
+    }
+
+    pub fn pmf(&self, index: usize) -> f32 {
+        self.pmf[index]
+    }
+}
+
+// Multiple importance sampling weight for a sample from a strategy with density `f_pdf` against
+// another strategy with density `g_pdf`, using the power heuristic with an exponent of two.
+pub fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+    if f.is_infinite() {
+        return 1.0;
+    }
+    if f + g == 0.0 {
+        return 0.0;
+    }
+    f / (f + g)
+}
+
+#[test]
+fn test_distribution_2d() {
+    let func = [0.0, 1.0, 2.0, 3.0, 4.0, 0.0];
+    let distrib = Distribution2D::new(&func, 3);
+
+    for u in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.99), (0.0, 0.0)] {
+        let ((x, y), pdf) = distrib.sample(u);
+        assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+        assert!(pdf > 0.0);
+        assert!((distrib.pdf(x, y) - pdf).abs() < 1e-5);
+    }
+
+    // Each cell is picked in proportion to its value; the total is 10 over six cells.
+    assert!((distrib.pdf(0.9, 0.1) - 2.0 / (10.0 / 6.0)).abs() < 1e-5);
+    assert_eq!(distrib.pdf(0.1, 0.1), 0.0);
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_alias_table() {
+    let weights = [1.0, 0.0, 3.0, 4.0];
+    let table = AliasTable::new(&weights);
+
+    let n = 8000;
+    let mut counts = [0; 4];
+    for i in 0..n {
+        let (index, pmf) = table.sample((i as f32 + 0.5) / n as f32).unwrap();
+        assert_eq!(pmf, weights[index] / 8.0);
+        counts[index] += 1;
+    }
+    for (count, weight) in counts.iter().zip(weights) {
+        assert!((*count as f32 / n as f32 - weight / 8.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,187 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use crate::{
+    onb::Onb,
+    spectrum::{xyz_to_linear_srgb, RGB_WAVELENGTHS},
+    vec3::{dot, random_in_cone},
+    Vec3,
+};
+
+// Angular radius of the sun seen from the ground.
+const SUN_ANGULAR_RADIUS_DEGREES: f32 = 0.2665;
+// Luminance of the sun above the atmosphere, in kcd/m².
+const SUN_LUMINANCE: f32 = 2.0e6;
+
+// Daylight from the analytic clear sky model of "A Practical Analytic Model for Daylight"
+// (Preetham, Shirley and Smits 1999), plus the sun as a small disk dimmed and reddened by the
+// atmosphere. Turbidity runs from 2 for a very clear sky to around 10 for haze.
+//
+// Luminance is in kcd/m², scaled by `intensity` which defaults to bringing a white surface in full
+// sun to about one. Below the horizon the sky keeps its horizon colour.
+pub struct Sky {
+    pub intensity: f32,
+    sun_direction: Vec3,
+    cos_sun_radius: f32,
+    sun_radiance: Vec3,   // Linear sRGB, before `intensity`
+    perez: [[f32; 5]; 3], // Distribution coefficients for Y, x and y
+    zenith: [f32; 3],     // Y, x and y straight up
+}
+
+impl Sky {
+    // The sun's elevation is in degrees above the horizon and its azimuth in degrees clockwise from
+    // -z seen from above, so 90 puts it towards +x.
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32) -> Self {
+        let elevation = elevation.clamp(0.0, 90.0).to_radians();
+        let azimuth = azimuth.to_radians();
+        let t = turbidity.max(1.0);
+        let theta_s = FRAC_PI_2 - elevation;
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let (s, s2, s3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+        let zenith = [
+            (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192,
+            t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+                + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+                + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886),
+            t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+                + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+                + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688),
+        ];
+
+        Sky {
+            intensity: 0.025,
+            sun_direction: Vec3::new(
+                elevation.cos() * azimuth.sin(),
+                elevation.sin(),
+                -elevation.cos() * azimuth.cos(),
+            ),
+            cos_sun_radius: SUN_ANGULAR_RADIUS_DEGREES.to_radians().cos(),
+            sun_radiance: SUN_LUMINANCE * Self::transmittance(theta_s, t),
+            perez,
+            zenith,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    // Fraction of sunlight reaching the ground through Rayleigh and aerosol scattering, per
+    // channel, from the appendix of the paper.
+    fn transmittance(theta_s: f32, turbidity: f32) -> Vec3 {
+        let relative_mass =
+            1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * turbidity - 0.04586;
+        let tau = |lambda_nm: f32| {
+            let lambda = lambda_nm / 1000.0; // In micrometres
+            let rayleigh = -0.008735 * lambda.powf(-4.08);
+            let aerosol = -beta * lambda.powf(-1.3);
+            ((rayleigh + aerosol) * relative_mass).exp()
+        };
+
+        Vec3::new(
+            tau(RGB_WAVELENGTHS[0]),
+            tau(RGB_WAVELENGTHS[1]),
+            tau(RGB_WAVELENGTHS[2]),
+        )
+    }
+
+    // Relative distribution of the sky over the zenith angle `theta` and angle `gamma` from the sun.
+    fn perez(coefficients: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+        let [a, b, c, d, e] = *coefficients;
+        let cos_gamma = gamma.cos();
+        (1.0 + a * (b / cos_theta).exp())
+            * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+
+    // Sky radiance without the sun disk, in linear sRGB.
+    fn sky_radiance(&self, direction: &Vec3) -> Vec3 {
+        let d = direction.normalize();
+        let d = Vec3::new(d.x(), d.y().max(0.0), d.z()).normalize();
+        let cos_theta = d.y().max(1e-3);
+        let gamma = dot(&d, &self.sun_direction).clamp(-1.0, 1.0).acos();
+        let theta_s = self.sun_direction.y().clamp(-1.0, 1.0).acos();
+
+        let value = |i: usize| {
+            self.zenith[i] * Self::perez(&self.perez[i], cos_theta, gamma)
+                / Self::perez(&self.perez[i], 1.0, theta_s)
+        };
+        let (luminance, x, y) = (value(0), value(1), value(2));
+
+        let xyz = Vec3::new(x * luminance / y, luminance, (1.0 - x - y) * luminance / y);
+        xyz_to_linear_srgb(&xyz)
+    }
+
+    // Radiance arriving from a direction, sun included, in linear sRGB.
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let mut radiance = self.sky_radiance(direction);
+        if dot(&direction.normalize(), &self.sun_direction) >= self.cos_sun_radius {
+            radiance += self.sun_radiance;
+        }
+        self.intensity * radiance
+    }
+
+    // Picks a direction on the sun disk, returning it with its solid angle density.
+    pub fn sample_sun(&self) -> (Vec3, f32) {
+        let uvw = Onb::new(&self.sun_direction);
+        let direction = uvw.transform(&random_in_cone(self.cos_sun_radius));
+        (direction, self.sun_pdf(&direction))
+    }
+
+    pub fn sun_pdf(&self, direction: &Vec3) -> f32 {
+        if dot(&direction.normalize(), &self.sun_direction) < self.cos_sun_radius {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - self.cos_sun_radius))
+    }
+}
+
+#[test]
+fn test_sky() {
+    use crate::color::luminance;
+
+    let sky = Sky::new(40.0, 90.0, 3.0).with_intensity(1.0);
+    assert!((sky.sun_direction() - Vec3::new(0.766, 0.643, 0.0)).length() < 1e-3);
+
+    // Straight up, the model gives back its zenith luminance.
+    let up = sky.radiance(&Vec3::new(0.0, 1.0, 0.0));
+    assert!((luminance(&up) - sky.zenith[0]).abs() < 1e-2 * sky.zenith[0]);
+
+    // The sky is brighter around the sun than away from it, and the sun brighter still.
+    let near_sun = sky.radiance(&Vec3::new(0.7, 0.5, 0.1));
+    let away = sky.radiance(&Vec3::new(-0.7, 0.5, 0.1));
+    assert!(luminance(&near_sun) > luminance(&away));
+
+    let (direction, pdf) = sky.sample_sun();
+    assert!(pdf > 0.0 && (sky.sun_pdf(&direction) - pdf).abs() < 1e-3 * pdf);
+    assert!(luminance(&sky.radiance(&direction)) > 1e5);
+}
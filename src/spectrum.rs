@@ -0,0 +1,236 @@
+use std::sync::OnceLock;
+
+use crate::vec3::{dot, Vec3};
+
+// Range of visible wavelengths traced in spectral mode, in nanometers.
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+// Wavelengths the red, green and blue channels stand for when interpolating RGB parameters such
+// as indices of refraction.
+pub const RGB_WAVELENGTHS: [f32; 3] = [650.0, 550.0, 450.0];
+
+// A hero wavelength plus two more spread evenly over the visible range. The radiance carried by a
+// spectral ray holds one value per wavelength, in place of the RGB channels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Wavelengths {
+    lambda: [f32; 3],
+    secondary_terminated: bool,
+}
+
+impl Wavelengths {
+    // Sample the hero wavelength uniformly from `u` in [0, 1).
+    pub fn sample(u: f32) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let rotate = |i: f32| {
+            let lambda = hero + i * range / 3.0;
+            if lambda > LAMBDA_MAX {
+                lambda - range
+            } else {
+                lambda
+            }
+        };
+
+        Wavelengths {
+            lambda: [hero, rotate(1.0), rotate(2.0)],
+            secondary_terminated: false,
+        }
+    }
+
+    pub fn lambda(&self, i: usize) -> f32 {
+        self.lambda[i]
+    }
+
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.secondary_terminated
+    }
+
+    // Drop the secondary wavelengths after a wavelength-dependent scattering event, such as
+    // dispersion, where they can't follow the hero's path. Returns the factor to scale the
+    // radiance by so the estimate stays unbiased.
+    pub fn terminate_secondary(&mut self) -> Vec3 {
+        if self.secondary_terminated {
+            return Vec3::new(1.0, 1.0, 1.0);
+        }
+        self.secondary_terminated = true;
+        Vec3::new(3.0, 0.0, 0.0)
+    }
+
+    // Evaluate the smooth reflectance spectrum of an RGB colour at each wavelength.
+    pub fn upsample(&self, rgb: &Vec3) -> Vec3 {
+        Vec3::new(
+            rgb_to_spectrum(rgb, self.lambda[0]),
+            rgb_to_spectrum(rgb, self.lambda[1]),
+            rgb_to_spectrum(rgb, self.lambda[2]),
+        )
+    }
+
+    // Linearly interpolate a quantity given for the red, green and blue wavelengths, for
+    // parameters that aren't colours, like complex indices of refraction.
+    pub fn interpolate(&self, rgb: &Vec3) -> Vec3 {
+        let at = |lambda: f32| {
+            let [r, g, b] = RGB_WAVELENGTHS;
+            if lambda >= r {
+                rgb.x()
+            } else if lambda >= g {
+                let t = (lambda - g) / (r - g);
+                rgb.y() + t * (rgb.x() - rgb.y())
+            } else if lambda >= b {
+                let t = (lambda - b) / (g - b);
+                rgb.z() + t * (rgb.y() - rgb.z())
+            } else {
+                rgb.z()
+            }
+        };
+        Vec3::new(at(self.lambda[0]), at(self.lambda[1]), at(self.lambda[2]))
+    }
+
+    // Convert radiance sampled at these wavelengths into linear sRGB. A constant spectrum of one
+    // maps to white on average.
+    pub fn to_rgb(&self, radiance: &Vec3) -> Vec3 {
+        let mut xyz = Vec3::default();
+        for i in 0..3 {
+            xyz += radiance[i] * cie_xyz(self.lambda[i]);
+        }
+        // Uniform wavelength sampling has a pdf of 1 / range, averaged over three wavelengths.
+        xyz *= (LAMBDA_MAX - LAMBDA_MIN) / 3.0;
+
+        xyz_to_linear_srgb(&xyz) / white_balance()
+    }
+}
+
+// Analytic fit of the CIE 1931 colour matching functions from "Simple Analytic Approximations to
+// the CIE XYZ Color Matching Functions" (Wyman, Sloan and Shirley 2013).
+pub fn cie_xyz(lambda: f32) -> Vec3 {
+    let g = |mu: f32, sigma1: f32, sigma2: f32| {
+        let sigma = if lambda < mu { sigma1 } else { sigma2 };
+        let t = (lambda - mu) / sigma;
+        f32::exp(-0.5 * t * t)
+    };
+
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_linear_srgb(xyz: &Vec3) -> Vec3 {
+    Vec3::new(
+        dot(&Vec3::new(3.2406, -1.5372, -0.4986), xyz),
+        dot(&Vec3::new(-0.9689, 1.8758, 0.0415), xyz),
+        dot(&Vec3::new(0.0557, -0.2040, 1.0570), xyz),
+    )
+}
+
+// Linear sRGB of a constant spectrum of one over the visible range, used to white balance the
+// spectral film.
+#[allow(clippy::cast_precision_loss)]
+fn white_balance() -> Vec3 {
+    static WHITE: OnceLock<Vec3> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let steps = 1000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f32;
+        let mut xyz = Vec3::default();
+        for i in 0..steps {
+            xyz += cie_xyz(LAMBDA_MIN + (i as f32 + 0.5) * step) * step;
+        }
+        xyz_to_linear_srgb(&xyz)
+    })
+}
+
+// Basis spectra from "An RGB to Spectrum Conversion for Reflectances" (Smits 1999), in ten bins
+// over the visible range.
+const SMITS_WHITE: [f32; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f32; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f32; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f32; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f32; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f32; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f32; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+// Linearly interpolate a binned spectrum between the bin centers.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn binned(spectrum: &[f32; 10], lambda: f32) -> f32 {
+    let bins = spectrum.len();
+    let x = ((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * bins as f32 - 0.5)
+        .clamp(0.0, (bins - 1) as f32);
+    let i = (x as usize).min(bins - 2);
+    let t = x - i as f32;
+    spectrum[i] + t * (spectrum[i + 1] - spectrum[i])
+}
+
+// Smits' RGB to spectrum conversion evaluated at a single wavelength.
+pub fn rgb_to_spectrum(rgb: &Vec3, lambda: f32) -> f32 {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    let at = |spectrum: &[f32; 10]| binned(spectrum, lambda);
+
+    if r <= g && r <= b {
+        // Red is the smallest component.
+        let base = r * at(&SMITS_WHITE);
+        if g <= b {
+            base + (g - r) * at(&SMITS_CYAN) + (b - g) * at(&SMITS_BLUE)
+        } else {
+            base + (b - r) * at(&SMITS_CYAN) + (g - b) * at(&SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        // Green is the smallest component.
+        let base = g * at(&SMITS_WHITE);
+        if r <= b {
+            base + (r - g) * at(&SMITS_MAGENTA) + (b - r) * at(&SMITS_BLUE)
+        } else {
+            base + (b - g) * at(&SMITS_MAGENTA) + (r - b) * at(&SMITS_RED)
+        }
+    } else {
+        // Blue is the smallest component.
+        let base = b * at(&SMITS_WHITE);
+        if r <= g {
+            base + (r - b) * at(&SMITS_YELLOW) + (g - r) * at(&SMITS_GREEN)
+        } else {
+            base + (g - b) * at(&SMITS_YELLOW) + (r - g) * at(&SMITS_RED)
+        }
+    }
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_round_trip() {
+    // Upsampling a colour and integrating it back should give roughly the same colour.
+    for rgb in [
+        Vec3::new(1.0, 1.0, 1.0),
+        Vec3::new(0.5, 0.5, 0.5),
+        Vec3::new(0.8, 0.2, 0.1),
+        Vec3::new(0.1, 0.6, 0.3),
+        Vec3::new(0.2, 0.3, 0.9),
+    ] {
+        let n = 3000;
+        let mut sum = Vec3::default();
+        for i in 0..n {
+            let lambda = Wavelengths::sample((i as f32 + 0.5) / n as f32);
+            sum += lambda.to_rgb(&lambda.upsample(&rgb));
+        }
+        let result = sum / n as f32;
+        assert!((result - rgb).length() < 0.1, "{rgb} -> {result}");
+    }
+}
@@ -1,4 +1,14 @@
-use crate::{material::Material, vec3::Vec3};
+use std::f32::consts::PI;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    vec3::{dot, random_to_sphere, random_vec, Vec3},
+};
 
 #[derive(Clone)]
 pub struct Sphere {
@@ -15,4 +25,118 @@ impl Sphere {
             mat,
         }
     }
+
+    fn get_sphere_uv(p: &Vec3) -> (f32, f32) {
+        // p: a given point on the sphere of radius one, centered at the origin.
+        // u: returned value [0,1] of angle around the Y axis from X=-1.
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+        let theta = f32::acos(-p.y().clamp(-1.0, 1.0));
+        let phi = f32::atan2(-p.z(), p.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // Partial derivatives of a point on the sphere along the coordinates of `get_sphere_uv`.
+    fn derivatives(&self, n: &Vec3) -> (Vec3, Vec3) {
+        let sin_theta = f32::sqrt(n.x() * n.x() + n.z() * n.z());
+        let dpdu = 2.0 * PI * self.radius * Vec3::new(n.z(), 0.0, -n.x());
+        if sin_theta < 1e-6 {
+            return (dpdu, Vec3::default());
+        }
+        let dpdv = PI
+            * self.radius
+            * Vec3::new(
+                -n.x() * n.y() / sin_theta,
+                sin_theta,
+                -n.y() * n.z() / sin_theta,
+            );
+        (dpdu, dpdv)
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        let oc = self.center - *r.origin();
+        let a = r.direction().length_squared();
+        let h = dot(r.direction(), &oc);
+        let c = oc.length_squared() - self.radius * self.radius;
+
+        let discriminant = h * h - a * c;
+        if discriminant < 0.0 {
+            return false;
+        }
+
+        let sqrtd = discriminant.sqrt();
+
+        // Find the nearest root that lies in the acceptable range.
+        let mut root = (h - sqrtd) / a;
+        if !ray_t.surrounds(root) {
+            root = (h + sqrtd) / a;
+
+            if !ray_t.surrounds(root) {
+                return false;
+            }
+        }
+
+        rec.t = root;
+        rec.p = r.at(rec.t);
+        let outward_normal = (rec.p - self.center) / self.radius;
+        rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = self.derivatives(&outward_normal);
+        rec.set_shading(dpdu, dpdv, outward_normal);
+        rec.mat = &self.mat;
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(self.center - rvec, self.center + rvec)
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        // This method only works for stationary spheres.
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(*origin, *direction),
+            &Interval::new(0.001, f32::INFINITY),
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        // From inside, the sphere surrounds the origin and is sampled over all directions.
+        let dist_squared = (self.center - *origin).length_squared();
+        if dist_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = f32::sqrt((1.0 - self.radius * self.radius / dist_squared).max(0.0));
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return random_vec();
+        }
+        let uvw = Onb::new(&direction);
+        uvw.transform(&random_to_sphere(self.radius, distance_squared))
+    }
+}
+
+#[test]
+fn test_pdf_from_inside() {
+    // An emitter around the origin is sampled over all directions, including away from its center.
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, -1.0), 5.0, Material::default());
+    let origin = Vec3::default();
+    for direction in [Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0)] {
+        assert!((sphere.pdf_value(&origin, &direction) - 1.0 / (4.0 * PI)).abs() < 1e-6);
+    }
+    let behind = (0..1000)
+        .filter(|_| sphere.random(&origin).z() > 0.0)
+        .count();
+    assert!(behind > 400);
 }
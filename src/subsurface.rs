@@ -0,0 +1,86 @@
+use crate::{
+    bsdf::{Bsdf, BsdfSample},
+    dielectric::RoughDielectric,
+    hittable::HitRecord,
+    medium::Medium,
+    ray::Ray,
+    Vec3,
+};
+
+// Random-walk subsurface scattering for skin, wax and marble. The surface is a dielectric
+// interface and the object's interior is a scattering medium that rays walk through until they
+// leave it again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Subsurface {
+    pub boundary: RoughDielectric,
+    pub medium: Medium,
+}
+
+impl Subsurface {
+    // `albedo` is the colour the surface should appear after multiple scattering and
+    // `mean_free_path` the average distance light travels between interactions, per channel.
+    pub fn new(
+        albedo: Vec3,
+        mean_free_path: Vec3,
+        anisotropy: f32,
+        refraction_index: f32,
+        roughness: f32,
+    ) -> Self {
+        let sigma_t = Vec3::new(
+            1.0 / mean_free_path.x(),
+            1.0 / mean_free_path.y(),
+            1.0 / mean_free_path.z(),
+        );
+        let single_scattering = Vec3::new(
+            single_scattering_albedo(albedo.x()),
+            single_scattering_albedo(albedo.y()),
+            single_scattering_albedo(albedo.z()),
+        );
+        let sigma_s = single_scattering * sigma_t;
+
+        Subsurface {
+            boundary: RoughDielectric::new(refraction_index, roughness),
+            medium: Medium {
+                sigma_a: sigma_t - sigma_s,
+                sigma_s,
+                g: anisotropy,
+            },
+        }
+    }
+}
+
+// Inverts the multiple scattering albedo of a semi-infinite medium to get the single scattering
+// albedo, using the fit from "Practical and Controllable Subsurface Scattering for Production Path
+// Tracing" (Chiang et al. 2016).
+fn single_scattering_albedo(albedo: f32) -> f32 {
+    let a = albedo.clamp(0.0, 0.999);
+    let s = 4.09712 + 4.20863 * a - f32::sqrt(9.59217 + 41.6808 * a + 17.7126 * a * a);
+    1.0 - s * s
+}
+
+impl Bsdf for Subsurface {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.boundary.sample(r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        self.boundary.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        self.boundary.pdf(r_in, rec, direction)
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        Some(self.medium)
+    }
+}
+
+#[test]
+fn test_single_scattering_albedo() {
+    // Fully absorbing and (almost) non-absorbing media map to themselves.
+    assert!(single_scattering_albedo(0.0).abs() < 1e-3);
+    assert!(single_scattering_albedo(0.999) > 0.99);
+    // Multiple scattering darkens a medium, so the single scattering albedo is higher.
+    assert!(single_scattering_albedo(0.5) > 0.5);
+}
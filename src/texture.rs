@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use crate::{image::Image, Vec3};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Texture {
+    Solid(Vec3),
+    Checker {
+        inv_scale: f32,
+        even: Arc<Texture>,
+        odd: Arc<Texture>,
+    },
+    Image(Arc<Image>),
+}
+
+impl Texture {
+    pub fn checker(scale: f32, even: Texture, odd: Texture) -> Self {
+        Texture::Checker {
+            inv_scale: 1.0 / scale,
+            even: Arc::new(even),
+            odd: Arc::new(odd),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn value(&self, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        match self {
+            Texture::Solid(albedo) => *albedo,
+            Texture::Checker {
+                inv_scale,
+                even,
+                odd,
+            } => {
+                let x = (inv_scale * p.x()).floor() as i32;
+                let y = (inv_scale * p.y()).floor() as i32;
+                let z = (inv_scale * p.z()).floor() as i32;
+
+                if (x + y + z) % 2 == 0 {
+                    even.value(u, v, p)
+                } else {
+                    odd.value(u, v, p)
+                }
+            }
+            Texture::Image(image) => image.sample(u, v),
+        }
+    }
+
+    // Value averaged over the whole texture.
+    pub fn average(&self) -> Vec3 {
+        match self {
+            Texture::Solid(albedo) => *albedo,
+            Texture::Checker { even, odd, .. } => 0.5 * (even.average() + odd.average()),
+            Texture::Image(image) => image.average(),
+        }
+    }
+}
+
+impl From<Vec3> for Texture {
+    fn from(albedo: Vec3) -> Self {
+        Texture::Solid(albedo)
+    }
+}
+
+impl From<f32> for Texture {
+    fn from(value: f32) -> Self {
+        Texture::Solid(Vec3::new(value, value, value))
+    }
+}
+
+impl Default for Texture {
+    fn default() -> Self {
+        Texture::Solid(Vec3::default())
+    }
+}
@@ -9,3 +9,8 @@ pub fn rand(min: f32, max: f32) -> f32 {
     let mut rng = rand::thread_rng();
     rng.gen_range(min..max)
 }
+
+pub fn rand_index(len: usize) -> usize {
+    let mut rng = rand::thread_rng();
+    rng.gen_range(0..len)
+}
@@ -1,10 +1,11 @@
 #![allow(clippy::cast_precision_loss)]
 use std::{
+    f32::consts::PI,
     fmt::Display,
-    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
-use crate::util::rand;
+use crate::util::{rand, rand_f32};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Vec3 {
@@ -25,7 +26,7 @@ impl Vec3 {
     }
 
     #[inline]
-    pub fn new(x: f32, y: f32, z: f32) -> Self {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
         Vec3 { x, y, z }
     }
 
@@ -108,6 +109,42 @@ pub fn random_vec() -> Vec3 {
     }
 }
 
+#[inline]
+pub fn random_cosine_direction() -> Vec3 {
+    // Returns a direction on the +z hemisphere with a density proportional to cos(theta).
+    let r1 = rand_f32();
+    let r2 = rand_f32();
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = f32::sqrt(1.0 - r2);
+
+    Vec3::new(x, y, z)
+}
+
+#[inline]
+pub fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3 {
+    // Returns a direction within the cone subtended by a sphere, with +z pointing at its center.
+    let cos_theta_max = f32::sqrt((1.0 - radius * radius / distance_squared).max(0.0));
+    random_in_cone(cos_theta_max)
+}
+
+#[inline]
+pub fn random_in_cone(cos_theta_max: f32) -> Vec3 {
+    // Returns a direction uniformly distributed within a cone around +z.
+    let r1 = rand_f32();
+    let r2 = rand_f32();
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+
+    let phi = 2.0 * PI * r1;
+    let sin_theta = f32::sqrt((1.0 - z * z).max(0.0));
+    let x = phi.cos() * sin_theta;
+    let y = phi.sin() * sin_theta;
+
+    Vec3::new(x, y, z)
+}
+
 impl Display for Vec3 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{} {} {}", self.x, self.y, self.z)
@@ -250,6 +287,20 @@ impl AddAssign for Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f32;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {index}"),
+        }
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Vec3;
     #[inline]